@@ -0,0 +1,110 @@
+use std::path::Path;
+
+/// Alternative names of contigs, to match the contig of a region with the naming used by each file.
+/// The chr prefix is added or removed and chrM/MT are matched automatically,
+/// additional equivalent names can be given as an alias table.
+#[derive(Debug, Clone, Default)]
+pub struct ContigAliases {
+    /// groups of names that refer to the same contig
+    groups: Vec<Vec<String>>,
+}
+
+impl ContigAliases {
+    /// Read an alias table, with equivalent names of a contig per line separated by tabs, spaces or commas,
+    /// such as the chromAlias.txt files of UCSC. Lines starting with # are ignored.
+    pub fn from_file(path: &Path) -> Result<ContigAliases, Box<dyn std::error::Error>> {
+        Ok(ContigAliases::parse(&std::fs::read_to_string(path)?))
+    }
+
+    fn parse(content: &str) -> ContigAliases {
+        ContigAliases {
+            groups: content
+                .lines()
+                .filter(|line| !line.starts_with('#'))
+                .map(|line| {
+                    line.split(|c: char| c == ',' || c.is_whitespace())
+                        .filter(|name| !name.is_empty())
+                        .map(str::to_string)
+                        .collect::<Vec<String>>()
+                })
+                .filter(|group| group.len() > 1)
+                .collect(),
+        }
+    }
+
+    /// All names the contig could have in a file, starting with the name itself
+    fn candidates(&self, name: &str) -> Vec<String> {
+        let mut names = with_prefix_toggled(vec![name.to_string()]);
+        for group in &self.groups {
+            if group.iter().any(|n| names.contains(n)) {
+                names.extend(group.iter().cloned());
+            }
+        }
+        let mut names = with_prefix_toggled(names);
+        if names.iter().any(|n| MITOCHONDRIAL.contains(&n.as_str())) {
+            names.extend(MITOCHONDRIAL.map(str::to_string));
+        }
+        let mut unique = vec![];
+        for name in names {
+            if !unique.contains(&name) {
+                unique.push(name);
+            }
+        }
+        unique
+    }
+
+    /// The name of the contig in a file, as the first candidate name for which exists returns true
+    pub fn resolve(&self, name: &str, exists: impl Fn(&str) -> bool) -> Option<String> {
+        self.candidates(name).into_iter().find(|n| exists(n))
+    }
+
+    /// Whether two names refer to the same contig
+    pub fn equivalent(&self, a: &str, b: &str) -> bool {
+        self.candidates(a).iter().any(|n| n == b)
+    }
+}
+
+const MITOCHONDRIAL: [&str; 4] = ["chrM", "MT", "M", "chrMT"];
+
+/// add the names with the chr prefix removed, or added if they don't have it
+fn with_prefix_toggled(names: Vec<String>) -> Vec<String> {
+    let toggled: Vec<String> = names
+        .iter()
+        .map(|name| match name.strip_prefix("chr") {
+            Some(stripped) => stripped.to_string(),
+            None => format!("chr{}", name),
+        })
+        .collect();
+    names.into_iter().chain(toggled).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let aliases = ContigAliases::default();
+        let header = ["1", "7", "MT", "chrUn_KI270302v1"];
+        let exists = |name: &str| header.contains(&name);
+        assert_eq!(aliases.resolve("chr7", exists).as_deref(), Some("7"));
+        assert_eq!(aliases.resolve("7", exists).as_deref(), Some("7"));
+        assert_eq!(aliases.resolve("chrM", exists).as_deref(), Some("MT"));
+        assert_eq!(aliases.resolve("chr8", exists), None);
+        assert!(aliases.equivalent("chr1", "1"));
+        assert!(!aliases.equivalent("chr1", "chr11"));
+    }
+
+    #[test]
+    fn test_alias_table() {
+        let aliases = ContigAliases::parse("# ucsc\tgenbank\nchr7\tCM000669.2\nchrX,CM000685.2\n");
+        let exists = |name: &str| name == "CM000669.2";
+        assert_eq!(
+            aliases.resolve("chr7", exists).as_deref(),
+            Some("CM000669.2")
+        );
+        assert_eq!(aliases.resolve("7", exists).as_deref(), Some("CM000669.2"));
+        assert_eq!(aliases.resolve("chr8", exists), None);
+        assert!(aliases.equivalent("CM000685.2", "chrX"));
+    }
+}
@@ -1,10 +1,14 @@
+use crate::aliases::ContigAliases;
+use crate::utils::Reg;
 use plotly::{
     color::Rgb,
     common::{Line, Mode},
     Scatter,
 };
+use rust_htslib::bgzf;
 use rust_htslib::tbx::{self, Read};
-use std::path::PathBuf;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
 
 pub struct Annot {
     pub begin: i64,
@@ -31,10 +35,20 @@ impl Annot {
 pub fn parse_bed(
     p: PathBuf,
     region: &crate::utils::Reg,
+    aliases: &ContigAliases,
 ) -> Result<Vec<Annot>, Box<dyn std::error::Error>> {
     let mut annotation: Vec<Annot> = vec![];
-    let mut tbx_reader = tbx::Reader::from_path(p)?;
-    let tid = tbx_reader.tid(&region.chrom)?;
+    let mut tbx_reader = tbx::Reader::from_path(&p)?;
+    let seqnames = tbx_reader.seqnames();
+    let Some(chrom) = aliases.resolve(&region.chrom, |c| seqnames.iter().any(|s| s == c)) else {
+        eprintln!(
+            "Warning: Contig {} not found in bed file {}",
+            region.chrom,
+            p.display()
+        );
+        return Ok(annotation);
+    };
+    let tid = tbx_reader.tid(&chrom)?;
     tbx_reader.fetch(tid, region.start.into(), region.end.into())?;
 
     // Read through all records in region.
@@ -58,3 +72,127 @@ pub fn parse_bed(
     }
     Ok(annotation)
 }
+
+/// Find the region of a gene by its name, in the name column of a bed file or the gene_name/gene_id of a gtf file.
+/// Features of the gene on its first contig are merged, e.g. when the bed file lists its exons.
+pub fn find_gene(
+    name: &str,
+    bed: Option<&Path>,
+    gtf: Option<&Path>,
+) -> Result<Option<Reg>, Box<dyn std::error::Error>> {
+    if let Some(bed) = bed {
+        if let Some(reg) = gene_in_bed(name, open_text(bed)?.lines())? {
+            return Ok(Some(reg));
+        }
+    }
+    if let Some(gtf) = gtf {
+        if let Some(reg) = gene_in_gtf(name, open_text(gtf)?.lines())? {
+            return Ok(Some(reg));
+        }
+    }
+    Ok(None)
+}
+
+/// open a plain or (b)gzipped text file, to read it line by line
+fn open_text(p: &Path) -> Result<BufReader<bgzf::Reader>, Box<dyn std::error::Error>> {
+    Ok(BufReader::new(bgzf::Reader::from_path(p)?))
+}
+
+fn gene_in_bed(
+    name: &str,
+    lines: impl Iterator<Item = std::io::Result<String>>,
+) -> std::io::Result<Option<Reg>> {
+    let mut reg = None;
+    for line in lines {
+        let line = line?;
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() > 3 && fields[3] == name {
+            if let (Ok(start), Ok(end)) = (fields[1].parse(), fields[2].parse()) {
+                merge_feature(&mut reg, fields[0], start, end);
+            }
+        }
+    }
+    Ok(reg)
+}
+
+/// gtf coordinates are 1-based and closed
+fn gene_in_gtf(
+    name: &str,
+    lines: impl Iterator<Item = std::io::Result<String>>,
+) -> std::io::Result<Option<Reg>> {
+    let quoted = format!("\"{}\"", name);
+    let mut reg = None;
+    for line in lines {
+        let line = line?;
+        if line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 9 {
+            continue;
+        }
+        let matches = fields[8].split(';').any(|attribute| {
+            let mut parts = attribute.split_whitespace();
+            matches!(parts.next(), Some("gene_name") | Some("gene_id"))
+                && parts.next() == Some(quoted.as_str())
+        });
+        if !matches {
+            continue;
+        }
+        let start = fields[3].parse::<u32>().ok().and_then(|s| s.checked_sub(1));
+        if let (Some(start), Ok(end)) = (start, fields[4].parse()) {
+            merge_feature(&mut reg, fields[0], start, end);
+        }
+    }
+    Ok(reg)
+}
+
+/// extend the region with a feature on the same contig, or start it with the first feature
+fn merge_feature(reg: &mut Option<Reg>, chrom: &str, start: u32, end: u32) {
+    match reg {
+        None => {
+            *reg = Some(Reg {
+                chrom: chrom.to_string(),
+                start,
+                end,
+            })
+        }
+        Some(reg) if reg.chrom == chrom => {
+            reg.start = reg.start.min(start);
+            reg.end = reg.end.max(end);
+        }
+        Some(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(content: &str) -> std::io::Lines<&[u8]> {
+        content.as_bytes().lines()
+    }
+
+    #[test]
+    fn test_gene_in_bed() {
+        let bed = "chr17\t43044294\t43045802\tBRCA1\nchr7\t1000\t2000\tGENE2\nchr17\t43124016\t43125483\tBRCA1\n";
+        let reg = gene_in_bed("BRCA1", lines(bed)).unwrap().unwrap();
+        assert_eq!(reg.to_string(), "chr17:43044294-43125483");
+        assert!(gene_in_bed("BRCA2", lines(bed)).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_gene_in_gtf() {
+        let gtf =
+            "#!genome-build GRCh38\n\
+             chr17\tHAVANA\tgene\t43044295\t43125483\t.\t-\t.\tgene_id \"ENSG00000012048\"; gene_name \"BRCA1\";\n\
+             chr17\tHAVANA\tgene\t1000\t2000\t.\t-\t.\tgene_id \"ENSG1\"; gene_name \"BRCA1-AS\";\n";
+        let reg = gene_in_gtf("BRCA1", lines(gtf)).unwrap().unwrap();
+        assert_eq!(reg.to_string(), "chr17:43044294-43125483");
+        assert_eq!(
+            gene_in_gtf("ENSG00000012048", lines(gtf)).unwrap().unwrap(),
+            reg
+        );
+        assert!(gene_in_gtf("BRCA", lines(gtf)).unwrap().is_none());
+    }
+}
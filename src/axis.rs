@@ -0,0 +1,160 @@
+use plotly::color::Rgb;
+use plotly::common::{DashType, Line, Mode};
+use plotly::Scatter;
+
+/// A stretch of the region, either kept at its true length or compressed to a fixed width
+#[derive(Debug, PartialEq)]
+struct Segment {
+    start: i64,
+    end: i64,
+    /// position of the start of the segment on the compressed axis
+    offset: i64,
+    width: i64,
+}
+
+impl Segment {
+    fn compressed(&self) -> bool {
+        self.width != self.end - self.start
+    }
+}
+
+/// A coordinate axis on which the space between features (such as introns) is collapsed to a fixed width,
+/// so that the features themselves get most of the plot
+#[derive(Debug)]
+pub struct CompressedAxis {
+    segments: Vec<Segment>,
+}
+
+impl CompressedAxis {
+    /// Create the axis for the region from start to end. Space between (merged) features
+    /// longer than gap_width is compressed to gap_width, shorter space is kept as is.
+    pub fn new(features: &[(i64, i64)], start: i64, end: i64, gap_width: i64) -> CompressedAxis {
+        let mut features: Vec<(i64, i64)> = features
+            .iter()
+            .map(|&(s, e)| (s.max(start), e.min(end)))
+            .filter(|(s, e)| e > s)
+            .collect();
+        features.sort_unstable();
+        let mut merged: Vec<(i64, i64)> = vec![];
+        for (s, e) in features {
+            match merged.last_mut() {
+                Some(last) if s <= last.1 => last.1 = last.1.max(e),
+                _ => merged.push((s, e)),
+            }
+        }
+
+        let mut segments = vec![];
+        let mut offset = 0;
+        let mut cursor = start;
+        let mut add = |s: i64, e: i64, width: i64| {
+            segments.push(Segment {
+                start: s,
+                end: e,
+                offset,
+                width,
+            });
+            offset += width;
+        };
+        for (s, e) in merged {
+            if s > cursor {
+                add(cursor, s, (s - cursor).min(gap_width));
+            }
+            add(s, e, e - s);
+            cursor = e;
+        }
+        if end > cursor {
+            add(cursor, end, (end - cursor).min(gap_width));
+        }
+        CompressedAxis { segments }
+    }
+
+    /// Map a genomic position to the compressed axis, positions outside the region are clamped to its bounds
+    pub fn map(&self, pos: i64) -> i64 {
+        let (Some(first), Some(last)) = (self.segments.first(), self.segments.last()) else {
+            return pos;
+        };
+        let pos = pos.clamp(first.start, last.end);
+        let segment = self
+            .segments
+            .iter()
+            .find(|segment| pos < segment.end)
+            .unwrap_or(last);
+        segment.offset + (pos - segment.start) * segment.width / (segment.end - segment.start)
+    }
+
+    /// Tick positions on the compressed axis, with the genomic position as label,
+    /// at the start of every segment that is kept at its true length and the end of the region
+    pub fn ticks(&self) -> (Vec<f64>, Vec<String>) {
+        let mut positions: Vec<i64> = self
+            .segments
+            .iter()
+            .filter(|segment| !segment.compressed())
+            .map(|segment| segment.start)
+            .collect();
+        positions.extend(self.segments.last().map(|segment| segment.end));
+        positions.dedup();
+        positions
+            .into_iter()
+            .map(|pos| (self.map(pos) as f64, pos.to_string()))
+            .unzip()
+    }
+
+    /// Vertical dotted lines marking where sequence was hidden, spanning the tracks from bottom to top
+    pub fn break_markers(&self, bottom: f64, top: f64) -> Vec<Box<Scatter<i64, f64>>> {
+        self.segments
+            .iter()
+            .filter(|segment| segment.compressed())
+            .map(|segment| {
+                let x = segment.offset + segment.width / 2;
+                Scatter::new(vec![x, x], vec![bottom, top])
+                    .mode(Mode::Lines)
+                    .show_legend(false)
+                    .hover_text(format!(
+                        "{} bp hidden ({}-{})",
+                        segment.end - segment.start,
+                        segment.start,
+                        segment.end
+                    ))
+                    .line(
+                        Line::new()
+                            .color(Rgb::new(192, 192, 192))
+                            .dash(DashType::Dot),
+                    )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compressed_axis() {
+        // two overlapping features and one separate one, with 1000 bp gaps around them
+        let axis =
+            CompressedAxis::new(&[(2000, 2100), (2050, 2200), (3200, 3300)], 1000, 4300, 100);
+        assert_eq!(axis.segments.len(), 5);
+        assert_eq!(axis.map(1000), 0);
+        assert_eq!(axis.map(1500), 50);
+        assert_eq!(axis.map(2000), 100);
+        assert_eq!(axis.map(2200), 300);
+        assert_eq!(axis.map(3250), 450);
+        assert_eq!(axis.map(4300), 600);
+        // clamped to the region
+        assert_eq!(axis.map(10), 0);
+        assert_eq!(axis.map(10000), 600);
+        assert_eq!(axis.break_markers(-2.0, 3.0).len(), 3);
+        let (values, labels) = axis.ticks();
+        assert_eq!(values, vec![100.0, 400.0, 600.0]);
+        assert_eq!(labels, vec!["2000", "3200", "4300"]);
+    }
+
+    #[test]
+    fn test_compressed_axis_short_gap() {
+        // a gap shorter than the gap width is kept at its true length
+        let axis = CompressedAxis::new(&[(100, 200), (250, 300)], 100, 300, 100);
+        assert_eq!(axis.map(250), 150);
+        assert!(axis.break_markers(0.0, 1.0).is_empty());
+    }
+}
@@ -0,0 +1,383 @@
+use crate::blocks::Blocks;
+use crate::error::PhasiusError;
+use crate::extract::{construct_blocks, file_stem, Contigs, ExtractOptions, Tracks};
+use rust_htslib::bgzf;
+use std::collections::HashSet;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// Phase block lists written by phasing tools, which can be plotted without the original bam/vcf
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlockListFormat {
+    /// whatshap stats --block-list
+    WhatsHap,
+    /// HapCUT2 haplotype block file
+    HapCut2,
+    /// bed file with a phase set per line, optionally with the phase set ID as name
+    Bed,
+}
+
+/// A phase block as listed in the file
+struct ListedBlock {
+    sample: Option<String>,
+    chrom: String,
+    phaseset: u32,
+    start: i64,
+    end: i64,
+    /// the number of phased variants in the block, if listed
+    variants: Option<usize>,
+}
+
+/// Check if the file is a block list, based on its first line that is not a comment or bed header.
+/// Both plain text and (b)gzip compressed files are supported.
+pub fn detect(file: &Path) -> Result<Option<BlockListFormat>, Box<dyn std::error::Error>> {
+    let reader = BufReader::new(bgzf::Reader::from_path(file)?);
+    for line in reader.split(b'\n') {
+        // binary files are not block lists
+        let Ok(line) = String::from_utf8(line?) else {
+            return Ok(None);
+        };
+        if line.starts_with("#sample\tchromosome\tphase_set") {
+            return Ok(Some(BlockListFormat::WhatsHap));
+        } else if is_comment(&line) {
+            continue;
+        } else if line.starts_with("BLOCK:") {
+            return Ok(Some(BlockListFormat::HapCut2));
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() >= 3 && fields[1].parse::<u64>().is_ok() && fields[2].parse::<u64>().is_ok()
+        {
+            return Ok(Some(BlockListFormat::Bed));
+        }
+        return Ok(None);
+    }
+    Ok(None)
+}
+
+fn is_comment(line: &str) -> bool {
+    line.trim().is_empty()
+        || line.starts_with('#')
+        || line.starts_with("track")
+        || line.starts_with("browser")
+}
+
+fn read_block_list(file: &Path, format: BlockListFormat) -> Result<Vec<ListedBlock>, PhasiusError> {
+    let reader = BufReader::new(bgzf::Reader::from_path(file).map_err(PhasiusError::htslib(file))?);
+    let lines = reader
+        .lines()
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(|_| PhasiusError::Open(file.to_path_buf()))?;
+    match format {
+        BlockListFormat::WhatsHap => parse_whatshap(&lines),
+        BlockListFormat::HapCut2 => parse_hapcut2(&lines),
+        BlockListFormat::Bed => parse_bed(&lines),
+    }
+    .map_err(|e| PhasiusError::Parse {
+        file: file.to_path_buf(),
+        message: e.to_string(),
+    })
+}
+
+/// The contigs in a block list, in order of appearance. As there is no header,
+/// the end of the last block on the contig is used as its length.
+pub fn contigs(file: &Path, format: BlockListFormat) -> Result<Contigs, PhasiusError> {
+    Ok(listed_contigs(&read_block_list(file, format)?))
+}
+
+fn listed_contigs(listed: &[ListedBlock]) -> Contigs {
+    let mut contigs: Contigs = vec![];
+    for block in listed {
+        let end = Some(block.end as u64);
+        match contigs.iter_mut().find(|(chrom, _)| *chrom == block.chrom) {
+            Some((_, length)) => *length = (*length).max(end),
+            None => contigs.push((block.chrom.clone(), end)),
+        }
+    }
+    contigs
+}
+
+pub fn blocks_from_block_list(
+    file: &Path,
+    format: BlockListFormat,
+    options: &ExtractOptions,
+    regions: &[crate::utils::Reg],
+) -> Result<Vec<Tracks>, PhasiusError> {
+    let listed = read_block_list(file, format)?;
+
+    // the tracks in the order the samples appear in the file, a block list without samples is a single track
+    let mut samples: Vec<Option<String>> = vec![];
+    for block in &listed {
+        if !samples.contains(&block.sample) {
+            samples.push(block.sample.clone());
+        }
+    }
+    if let Some(requested) = &options.samples {
+        samples.retain(|sample| sample.as_ref().is_none_or(|s| requested.contains(s)));
+    }
+    if samples.is_empty() {
+        samples.push(None);
+    }
+    let use_sample_names = samples.len() > 1 || options.name_from_header;
+    let contigs = listed_contigs(&listed);
+
+    Ok(regions
+        .iter()
+        .map(|region| {
+            // the name of the contig of the region in the block list
+            let chrom = options.aliases.resolve(&region.chrom, |c| {
+                contigs.iter().any(|(contig, _)| contig == c)
+            });
+            samples
+                .iter()
+                .map(|sample| {
+                    let name = match sample {
+                        Some(sample) if use_sample_names => sample.clone(),
+                        _ => file_stem(file),
+                    };
+                    let mut sample_blocks: Vec<&ListedBlock> = listed
+                        .iter()
+                        .filter(|block| {
+                            &block.sample == sample && Some(&block.chrom) == chrom.as_ref()
+                        })
+                        .filter(|block| {
+                            block.end > region.start as i64 && block.start < region.end as i64
+                        })
+                        .collect();
+                    sample_blocks.sort_by_key(|block| block.start);
+                    let blocks = sample_blocks
+                        .iter()
+                        .map(|block| (block.start, block.end, block.phaseset));
+                    match construct_blocks(blocks, name.clone(), options.max_gap) {
+                        Some(blocks) => blocks
+                            .into_iter()
+                            .map(|block| with_variant_support(block, &sample_blocks))
+                            .collect(),
+                        None => {
+                            eprintln!(
+                                "Warning: No phase blocks found for {} in {} of block list {}",
+                                name,
+                                region,
+                                file.display()
+                            );
+                            vec![Blocks {
+                                start: 0,
+                                end: 0,
+                                name,
+                                empty: true,
+                                ..Default::default()
+                            }]
+                        }
+                    }
+                })
+                .collect()
+        })
+        .collect())
+}
+
+/// The records of a block constructed from a block list are the listed blocks.
+/// If those list their variants, use these as supporting records instead.
+fn with_variant_support(block: Blocks, listed: &[&ListedBlock]) -> Blocks {
+    let parts: Vec<&&ListedBlock> = listed
+        .iter()
+        .filter(|l| l.phaseset == block.phaseset && l.start >= block.start && l.end <= block.end)
+        .collect();
+    if parts.iter().any(|l| l.variants.is_none()) {
+        return block;
+    }
+    Blocks {
+        support: parts.iter().filter_map(|l| l.variants).sum(),
+        // the first and last variant define the listed block
+        last_record: block.end - 1,
+        ..block
+    }
+}
+
+/// parse the output of whatshap stats --block-list, with columns
+/// sample, chromosome, phase_set, from, to (1-based positions of the first and last variant) and variants
+fn parse_whatshap(lines: &[String]) -> Result<Vec<ListedBlock>, Box<dyn std::error::Error>> {
+    let mut blocks = vec![];
+    for line in lines.iter().filter(|line| !is_comment(line)) {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 5 {
+            return Err(format!("Invalid line in whatshap block list: {}", line).into());
+        }
+        let from: i64 = fields[3].parse()?;
+        blocks.push(ListedBlock {
+            sample: Some(fields[0].to_string()),
+            chrom: fields[1].to_string(),
+            phaseset: fields[2].parse()?,
+            start: from - 1,
+            end: fields[4].parse()?,
+            variants: fields.get(5).and_then(|v| v.parse().ok()),
+        });
+    }
+    Ok(blocks)
+}
+
+/// parse a HapCUT2 block file, in which each block starts with a "BLOCK:" line,
+/// followed by a line per variant (index, haplotype 1, haplotype 2, chromosome, 1-based position, ...)
+/// and ends with a line of asterisks. Unphased variants have a '-' as haplotype.
+/// Like in the VCF output of HapCUT2, the phase set is the position of the first phased variant.
+fn parse_hapcut2(lines: &[String]) -> Result<Vec<ListedBlock>, Box<dyn std::error::Error>> {
+    let mut blocks = vec![];
+    let mut current: Option<ListedBlock> = None;
+    for line in lines {
+        if line.starts_with("BLOCK:") || line.starts_with("****") {
+            blocks.extend(current.take());
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 5 {
+            continue;
+        }
+        if fields[1] == "-" || fields[2] == "-" {
+            continue;
+        }
+        let position: i64 = fields[4].parse()?;
+        match &mut current {
+            Some(block) => {
+                block.start = block.start.min(position - 1);
+                block.end = block.end.max(position);
+                block.variants = block.variants.map(|v| v + 1);
+            }
+            None => {
+                current = Some(ListedBlock {
+                    sample: None,
+                    chrom: fields[3].to_string(),
+                    phaseset: position as u32,
+                    start: position - 1,
+                    end: position,
+                    variants: Some(1),
+                })
+            }
+        }
+    }
+    blocks.extend(current);
+    Ok(blocks)
+}
+
+/// parse a bed file with a phase set per line, using its name as phase set ID if it is a number.
+/// Lines without such a name get the lowest ID that is not the name of another line.
+fn parse_bed(lines: &[String]) -> Result<Vec<ListedBlock>, Box<dyn std::error::Error>> {
+    let mut blocks = vec![];
+    let mut named = HashSet::new();
+    for line in lines.iter().filter(|line| !is_comment(line)) {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 3 {
+            return Err(format!("Invalid line in bed file: {}", line).into());
+        }
+        let phaseset: Option<u32> = fields.get(3).and_then(|name| name.parse().ok());
+        named.extend(phaseset);
+        blocks.push((
+            phaseset,
+            ListedBlock {
+                sample: None,
+                chrom: fields[0].to_string(),
+                phaseset: 0,
+                start: fields[1].parse()?,
+                end: fields[2].parse()?,
+                variants: None,
+            },
+        ));
+    }
+    let mut unnamed = (0..).filter(|id| !named.contains(id));
+    Ok(blocks
+        .into_iter()
+        .map(|(phaseset, block)| ListedBlock {
+            phaseset: phaseset.or_else(|| unnamed.next()).unwrap_or_default(),
+            ..block
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(content: &str) -> Vec<String> {
+        content.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_parse_whatshap() {
+        let blocks = parse_whatshap(&lines(
+            "#sample\tchromosome\tphase_set\tfrom\tto\tvariants\nS1\tchr1\t1001\t1001\t5000\t12\nS2\tchr1\t2001\t2001\t9000\t30\n",
+        ))
+        .unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[1].sample.as_deref(), Some("S2"));
+        assert_eq!(blocks[1].phaseset, 2001);
+        assert_eq!(blocks[1].start, 2000);
+        assert_eq!(blocks[1].end, 9000);
+        assert_eq!(blocks[1].variants, Some(30));
+    }
+
+    #[test]
+    fn test_parse_hapcut2() {
+        let blocks = parse_hapcut2(&lines(
+            "BLOCK: offset: 1 len: 3 phased: 2 SPAN: 400 fragments 2\n\
+             1\t0\t1\tchr1\t1000\tA\tG\t0/1\n\
+             2\t-\t-\tchr1\t1200\tC\tT\t0/1\n\
+             3\t1\t0\tchr1\t1400\tG\tA\t0/1\n\
+             ********\n\
+             BLOCK: offset: 4 len: 2 phased: 2 SPAN: 100 fragments 1\n\
+             4\t1\t0\tchr1\t3000\tG\tA\t0/1\n\
+             5\t0\t1\tchr1\t3100\tG\tA\t0/1\n\
+             ********\n",
+        ))
+        .unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].chrom, "chr1");
+        assert_eq!(blocks[0].phaseset, 1000);
+        assert_eq!((blocks[0].start, blocks[0].end), (999, 1400));
+        assert_eq!((blocks[1].start, blocks[1].end), (2999, 3100));
+        assert_eq!(blocks[0].variants, Some(2));
+    }
+
+    #[test]
+    fn test_listed_contigs() {
+        let listed = parse_bed(&lines("chr2\t100\t200\nchr1\t300\t400\nchr2\t500\t600\n")).unwrap();
+        assert_eq!(
+            listed_contigs(&listed),
+            vec![
+                ("chr2".to_string(), Some(600)),
+                ("chr1".to_string(), Some(400))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_with_variant_support() {
+        let listed = parse_whatshap(&lines(
+            "S1\tchr1\t1001\t1001\t5000\t12\nS1\tchr1\t1001\t8001\t9000\t3\n",
+        ))
+        .unwrap();
+        let listed: Vec<&ListedBlock> = listed.iter().collect();
+        let block = Blocks {
+            start: 1000,
+            end: 9000,
+            phaseset: 1001,
+            support: 2,
+            ..Default::default()
+        };
+        let block = with_variant_support(block, &listed);
+        assert_eq!(block.support, 15);
+        assert_eq!(block.last_record, 8999);
+    }
+
+    #[test]
+    fn test_parse_bed() {
+        let blocks = parse_bed(&lines(
+            "track name=phasesets\nchr1\t100\t200\t1\nchr1\t300\t400\nchr1\t500\t600\tblock\nchr1\t700\t800\t0\n",
+        ))
+        .unwrap();
+        assert_eq!(blocks.len(), 4);
+        assert_eq!(blocks[0].phaseset, 1);
+        // the other lines do not reuse the IDs named on any line
+        assert_eq!(blocks[1].phaseset, 2);
+        assert_eq!(blocks[2].phaseset, 3);
+        assert_eq!(blocks[3].phaseset, 0);
+        assert_eq!((blocks[1].start, blocks[1].end), (300, 400));
+        assert!(parse_bed(&lines("chr1\t100\n")).is_err());
+    }
+}
@@ -1,22 +1,45 @@
-use plotly::common::{Line, Marker, Mode};
+use crate::axis::CompressedAxis;
+use plotly::color::Rgb;
+use plotly::common::{DashType, Line, Marker, Mode};
 use plotly::Scatter;
 
+#[derive(Debug, Clone, Default)]
 pub struct Blocks {
     pub start: i64,
     pub end: i64,
     pub name: String,
     pub empty: bool,
+    /// group of the sample, from the sample sheet
+    pub group: Option<String>,
+    /// additional sample sheet columns of the sample, as (column, value)
+    pub metadata: Vec<(String, String)>,
+    /// the block overlaps with a block of another phase set of the same sample,
+    /// e.g. because phase sets are interleaved
+    pub overlapping: bool,
+    /// the phase set of the block, shared by the blocks a phase set is split in at large gaps
+    pub phaseset: u32,
+    /// the number of reads or variants supporting the block
+    pub support: usize,
+    /// the start position of the first and last supporting read or variant
+    pub first_record: i64,
+    pub last_record: i64,
+    /// the reason extraction failed for this sample, which is then drawn as a failed row spanning the region
+    pub error: Option<String>,
 }
 
 impl Blocks {
     pub fn plot(
         &self,
-        height: usize,
+        height: f64,
         color: String,
         show_legend: bool,
         width: Option<usize>,
         limits: Option<(u32, u32)>,
-    ) -> Box<plotly::Scatter<i64, usize>> {
+        axis: Option<&CompressedAxis>,
+    ) -> Box<plotly::Scatter<i64, f64>> {
+        if let Some(error) = &self.error {
+            return self.plot_failed(height, error, axis);
+        }
         if self.empty {
             // in the current implementation, empty blocks are not plotted, as they are filtered out before the call to .plot()
             // however, I will leave this in, as things might change in the future
@@ -29,20 +52,74 @@ impl Blocks {
         } else {
             (self.start, self.end)
         };
-        match width {
-            Some(width) => Scatter::new(vec![start, end], vec![height, height])
-                .mode(Mode::Lines)
-                .name(&self.name)
-                .legend_group(&self.name)
-                .show_legend(show_legend)
-                .line(Line::new().width(width as f64))
-                .marker(Marker::new().color(color)),
-            None => Scatter::new(vec![start, end], vec![height, height])
-                .mode(Mode::Lines)
-                .name(&self.name)
-                .legend_group(&self.name)
-                .show_legend(show_legend)
-                .marker(Marker::new().color(color)),
+        // on a compressed axis, the block is drawn at its remapped position
+        let (start, end) = match axis {
+            Some(axis) => (axis.map(start), axis.map(end)),
+            None => (start, end),
+        };
+        // samples of the same group share a legend group, so they can be toggled together
+        let legend_group = self.group.as_ref().unwrap_or(&self.name);
+        let mut line = Line::new();
+        if let Some(width) = width {
+            line = line.width(width as f64);
+        }
+        // overlapping blocks are dashed, to distinguish them from blocks drawn on top of each other
+        if self.overlapping {
+            line = line.dash(DashType::Dash);
+        }
+        Scatter::new(vec![start, end], vec![height, height])
+            .mode(Mode::Lines)
+            .name(&self.name)
+            .legend_group(legend_group)
+            .show_legend(show_legend)
+            .hover_text(self.hover_text())
+            .line(line)
+            .marker(Marker::new().color(color))
+    }
+
+    /// a dotted gray line with the error as hover text
+    fn plot_failed(
+        &self,
+        height: f64,
+        error: &str,
+        axis: Option<&CompressedAxis>,
+    ) -> Box<plotly::Scatter<i64, f64>> {
+        let (start, end) = match axis {
+            Some(axis) => (axis.map(self.start), axis.map(self.end)),
+            None => (self.start, self.end),
+        };
+        Scatter::new(vec![start, end], vec![height, height])
+            .mode(Mode::Lines)
+            .name(format!("{} (failed)", self.name))
+            .show_legend(true)
+            .hover_text(format!("{}<br>failed: {}", self.name, error))
+            .line(
+                Line::new()
+                    .dash(DashType::Dot)
+                    .color(Rgb::new(160, 160, 160)),
+            )
+    }
+
+    fn hover_text(&self) -> String {
+        let mut text = format!(
+            "{}<br>{}-{}<br>phase set: {}<br>supporting records: {} (starting {}-{})",
+            self.name,
+            self.start,
+            self.end,
+            self.phaseset,
+            self.support,
+            self.first_record,
+            self.last_record
+        );
+        if self.overlapping {
+            text.push_str("<br>overlaps another phase set");
+        }
+        if let Some(group) = &self.group {
+            text.push_str(&format!("<br>group: {}", group));
+        }
+        for (column, value) in &self.metadata {
+            text.push_str(&format!("<br>{}: {}", column, value));
         }
+        text
     }
 }
@@ -0,0 +1,340 @@
+use crate::blocks::Blocks;
+use crate::error::PhasiusError;
+use crate::extract::{self, Contigs, ExtractOptions, Tracks};
+use crate::utils::{split_explicit_index, Reg};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const EXTENSION: &str = "blocks";
+
+/// An on-disk cache of the blocks extracted from an input in a region, so that re-running with other
+/// plotting or summary options does not read the inputs again. An entry is keyed by the input path, its size
+/// and modification time, the region and a hash of the extraction options, and is stored in a file named after
+/// a hash of the key. The full key is stored in the file as well, and has to match for the entry to be used.
+/// The contigs in the header of an input are cached as well, so that a re-run in which all blocks of an input
+/// are cached does not open the input to check it.
+pub struct BlockCache {
+    dir: PathBuf,
+}
+
+impl BlockCache {
+    pub fn new(dir: &Path) -> std::io::Result<BlockCache> {
+        std::fs::create_dir_all(dir)?;
+        Ok(BlockCache {
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    /// Remove all entries, returning the number of entries removed
+    pub fn clear(&self) -> std::io::Result<usize> {
+        let mut removed = 0;
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == EXTENSION) {
+                std::fs::remove_file(path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Get the blocks of a file in each of the regions, from the cache where possible.
+    /// Regions that are not cached are extracted together and added to the cache.
+    pub fn get_blocks(
+        &self,
+        file: &Path,
+        options: &ExtractOptions,
+        regions: &[Reg],
+    ) -> Result<Vec<Tracks>, PhasiusError> {
+        let keys: Vec<Option<String>> = regions
+            .iter()
+            .map(|region| cache_key(file, options, region))
+            .collect();
+        let mut tracks_per_region: Vec<Option<Tracks>> = keys
+            .iter()
+            .map(|key| key.as_ref().and_then(|key| self.load(key)))
+            .collect();
+        let missing: Vec<Reg> = regions
+            .iter()
+            .zip(&tracks_per_region)
+            .filter(|(_, tracks)| tracks.is_none())
+            .map(|(region, _)| region.clone())
+            .collect();
+        log::debug!(
+            "Found {} of {} regions of {} in the cache",
+            regions.len() - missing.len(),
+            regions.len(),
+            file.display()
+        );
+        if !missing.is_empty() {
+            let mut extracted =
+                extract::get_blocks(&file.to_path_buf(), options, &missing)?.into_iter();
+            for (slot, key) in tracks_per_region.iter_mut().zip(&keys) {
+                if slot.is_some() {
+                    continue;
+                }
+                let tracks = extracted.next().unwrap_or_default();
+                if let Some(key) = key {
+                    self.write(key, to_lines(&tracks, key));
+                }
+                *slot = Some(tracks);
+            }
+        }
+        Ok(tracks_per_region
+            .into_iter()
+            .map(Option::unwrap_or_default)
+            .collect())
+    }
+
+    /// Whether the blocks of a file are cached in all regions
+    pub fn has_blocks(&self, file: &Path, options: &ExtractOptions, regions: &[Reg]) -> bool {
+        regions
+            .iter()
+            .all(|region| cache_key(file, options, region).is_some_and(|key| self.has_entry(&key)))
+    }
+
+    /// The contigs of a file, if its header was cached by an earlier run
+    pub fn get_contigs(&self, file: &Path) -> Option<Contigs> {
+        let key = format!("{}\tcontigs", file_key(file)?);
+        let content = std::fs::read_to_string(self.entry(&key)).ok()?;
+        let mut lines = content.lines();
+        if lines.next()?.strip_prefix("#key\t")? != key {
+            return None;
+        }
+        lines
+            .map(|line| {
+                let (chrom, length) = line.split_once('\t')?;
+                let length = match length {
+                    "" => None,
+                    length => Some(length.parse().ok()?),
+                };
+                Some((chrom.to_string(), length))
+            })
+            .collect()
+    }
+
+    pub fn store_contigs(&self, file: &Path, contigs: &Contigs) {
+        let Some(key) = file_key(file).map(|key| format!("{}\tcontigs", key)) else {
+            return;
+        };
+        let mut lines = format!("#key\t{}\n", key);
+        for (chrom, length) in contigs {
+            lines.push_str(&format!(
+                "{}\t{}\n",
+                chrom,
+                length.map_or(String::new(), |l| l.to_string())
+            ));
+        }
+        self.write(&key, lines);
+    }
+
+    fn entry(&self, key: &str) -> PathBuf {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        self.dir
+            .join(format!("{:016x}.{}", hasher.finish(), EXTENSION))
+    }
+
+    /// whether an entry for the key exists, without reading all of it
+    fn has_entry(&self, key: &str) -> bool {
+        let Ok(file) = std::fs::File::open(self.entry(key)) else {
+            return false;
+        };
+        let mut first = String::new();
+        std::io::BufReader::new(file).read_line(&mut first).is_ok()
+            && first.trim_end_matches('\n').strip_prefix("#key\t") == Some(key)
+    }
+
+    /// a missing, unreadable or outdated entry is a cache miss
+    fn load(&self, key: &str) -> Option<Tracks> {
+        let content = std::fs::read_to_string(self.entry(key)).ok()?;
+        let tracks = from_lines(&content, key);
+        if tracks.is_none() {
+            log::debug!("Ignoring outdated or invalid cache entry for {}", key);
+        }
+        tracks
+    }
+
+    /// written to a temporary file first, as the same entry can be written by multiple threads
+    fn write(&self, key: &str, content: String) {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let entry = self.entry(key);
+        let tmp = entry.with_extension(format!(
+            "{}.{}.tmp",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        if let Err(e) = std::fs::write(&tmp, content).and_then(|_| std::fs::rename(tmp, entry)) {
+            eprintln!(
+                "Warning: Unable to write to block cache {}: {}",
+                self.dir.display(),
+                e
+            );
+        }
+    }
+}
+
+/// The key of the blocks of a file in a region, or None if the file cannot be inspected.
+/// The options are hashed to keep the key short, and the number of decompression threads
+/// does not change the blocks, and is not part of the key.
+fn cache_key(file: &Path, options: &ExtractOptions, region: &Reg) -> Option<String> {
+    let options = ExtractOptions {
+        threads: 0,
+        ..options.clone()
+    };
+    let mut hasher = DefaultHasher::new();
+    format!("{:?}", options).hash(&mut hasher);
+    Some(format!(
+        "{}\t{}\toptions:{:016x}",
+        file_key(file)?,
+        region,
+        hasher.finish()
+    ))
+}
+
+/// The version, path, size and modification time of a file, so that entries of a modified file are not used
+fn file_key(file: &Path) -> Option<String> {
+    let (path, _) = split_explicit_index(file);
+    let metadata = std::fs::metadata(path).ok()?;
+    let modified = metadata
+        .modified()
+        .ok()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?;
+    Some(format!(
+        "{}\t{}\t{}\t{}",
+        env!("CARGO_PKG_VERSION"),
+        std::fs::canonicalize(path).ok()?.display(),
+        metadata.len(),
+        modified.as_nanos()
+    ))
+}
+
+fn to_lines(tracks: &Tracks, key: &str) -> String {
+    let mut lines = format!("#key\t{}\n#tracks\t{}\n", key, tracks.len());
+    for (index, track) in tracks.iter().enumerate() {
+        for block in track {
+            lines.push_str(&format!(
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                index,
+                block.start,
+                block.end,
+                block.name,
+                block.empty,
+                block.overlapping,
+                block.phaseset,
+                block.support,
+                block.first_record,
+                block.last_record
+            ));
+        }
+    }
+    lines
+}
+
+/// the tracks of an entry, or None if it is not for this key or cannot be parsed
+fn from_lines(content: &str, key: &str) -> Option<Tracks> {
+    let mut lines = content.lines();
+    if lines.next()?.strip_prefix("#key\t")? != key {
+        return None;
+    }
+    let num_tracks: usize = lines.next()?.strip_prefix("#tracks\t")?.parse().ok()?;
+    let mut tracks: Tracks = vec![vec![]; num_tracks];
+    for line in lines {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 10 {
+            return None;
+        }
+        let index: usize = fields[0].parse().ok()?;
+        tracks.get_mut(index)?.push(Blocks {
+            start: fields[1].parse().ok()?,
+            end: fields[2].parse().ok()?,
+            name: fields[3].to_string(),
+            empty: fields[4].parse().ok()?,
+            overlapping: fields[5].parse().ok()?,
+            phaseset: fields[6].parse().ok()?,
+            support: fields[7].parse().ok()?,
+            first_record: fields[8].parse().ok()?,
+            last_record: fields[9].parse().ok()?,
+            ..Default::default()
+        });
+    }
+    Some(tracks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_lines() {
+        let tracks = vec![
+            vec![Blocks {
+                start: 10,
+                end: 20,
+                name: "a".to_string(),
+                phaseset: 10,
+                support: 3,
+                first_record: 10,
+                last_record: 15,
+                ..Default::default()
+            }],
+            vec![Blocks {
+                name: "b".to_string(),
+                empty: true,
+                ..Default::default()
+            }],
+        ];
+        let lines = to_lines(&tracks, "key");
+        let parsed = from_lines(&lines, "key").unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!((parsed[0][0].start, parsed[0][0].end), (10, 20));
+        assert_eq!(parsed[0][0].last_record, 15);
+        assert!(parsed[1][0].empty);
+        assert!(from_lines(&lines, "other key").is_none());
+    }
+
+    #[test]
+    fn test_block_cache() {
+        let dir = Path::new("test-data/cache-test");
+        let cache = BlockCache::new(dir).unwrap();
+        cache.clear().unwrap();
+        let options = ExtractOptions {
+            threads: 1,
+            ..Default::default()
+        };
+        let file = Path::new("test-data/small-test-phased.bam");
+        let regions = [Reg {
+            chrom: "chr7".to_string(),
+            start: 152743763,
+            end: 156779243,
+        }];
+        let extracted = cache.get_blocks(file, &options, &regions).unwrap();
+        let key = cache_key(file, &options, &regions[0]).unwrap();
+        assert!(!key.contains("ExtractOptions"));
+        let cached = cache.load(&key).unwrap();
+        assert_eq!(cached.len(), extracted[0].len());
+        assert!(cache.has_blocks(file, &options, &regions));
+        assert_eq!(cached[0].len(), extracted[0][0].len());
+        // other filters are another entry
+        let options = ExtractOptions {
+            min_mapq: 60,
+            ..options
+        };
+        assert!(cache
+            .load(&cache_key(file, &options, &regions[0]).unwrap())
+            .is_none());
+        assert!(!cache.has_blocks(file, &options, &regions));
+        let contigs = vec![
+            ("chr7".to_string(), Some(159345973)),
+            ("chrM".to_string(), None),
+        ];
+        cache.store_contigs(file, &contigs);
+        assert_eq!(cache.get_contigs(file), Some(contigs));
+        assert_eq!(cache.clear().unwrap(), 2);
+        std::fs::remove_dir(dir).unwrap();
+    }
+}
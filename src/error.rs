@@ -0,0 +1,120 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Errors when extracting phase blocks from an input file
+#[derive(Debug)]
+pub enum PhasiusError {
+    /// the file does not exist or cannot be read
+    Open(PathBuf),
+    UnsupportedFormat(PathBuf),
+    /// the bam/cram/vcf/bcf has no (readable) index
+    MissingIndex(PathBuf),
+    ContigNotFound {
+        contig: String,
+        file: PathBuf,
+    },
+    /// the region extends beyond the end of the contig
+    RegionOutOfBounds {
+        region: String,
+        length: u64,
+        file: PathBuf,
+    },
+    /// the PS tag of a read is not an integer
+    BadPsType {
+        file: PathBuf,
+        found: String,
+    },
+    /// reading, fetching or decoding failed in htslib
+    Htslib {
+        file: PathBuf,
+        source: rust_htslib::errors::Error,
+    },
+    /// inputs (or the fasta index) disagree about the length of a contig, e.g. when mixing reference builds
+    LengthMismatch {
+        contig: String,
+        /// the files with each length
+        lengths: Vec<(u64, Vec<PathBuf>)>,
+    },
+    /// a line of a block list could not be parsed
+    Parse {
+        file: PathBuf,
+        message: String,
+    },
+}
+
+impl PhasiusError {
+    /// wrap an htslib error with the file it occurred in
+    pub fn htslib(file: &Path) -> impl Fn(rust_htslib::errors::Error) -> PhasiusError + '_ {
+        move |source| PhasiusError::Htslib {
+            file: file.to_path_buf(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for PhasiusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PhasiusError::Open(file) => write!(f, "Unable to open {}", file.display()),
+            PhasiusError::UnsupportedFormat(file) => write!(
+                f,
+                "Unsupported file format of {}, expected a bam, cram, sam, vcf, bcf or block list file",
+                file.display()
+            ),
+            PhasiusError::MissingIndex(file) => {
+                write!(f, "No index found for {}", file.display())
+            }
+            PhasiusError::ContigNotFound { contig, file } => {
+                write!(f, "Contig {} not found in {}", contig, file.display())
+            }
+            PhasiusError::RegionOutOfBounds {
+                region,
+                length,
+                file,
+            } => write!(
+                f,
+                "Region {} extends beyond the length of the contig ({}) in {}",
+                region,
+                length,
+                file.display()
+            ),
+            PhasiusError::BadPsType { file, found } => write!(
+                f,
+                "Unexpected type of PS tag in {}: {}",
+                file.display(),
+                found
+            ),
+            PhasiusError::Htslib { file, source } => {
+                write!(f, "Failure reading {}: {}", file.display(), source)
+            }
+            PhasiusError::LengthMismatch { contig, lengths } => {
+                let lengths: Vec<String> = lengths
+                    .iter()
+                    .map(|(length, files)| {
+                        let files: Vec<String> =
+                            files.iter().map(|f| f.display().to_string()).collect();
+                        format!("{} in {}", length, files.join(", "))
+                    })
+                    .collect();
+                write!(
+                    f,
+                    "Contig {} has a different length in the inputs, suggesting different reference builds: {}",
+                    contig,
+                    lengths.join("; ")
+                )
+            }
+            PhasiusError::Parse { file, message } => {
+                write!(f, "Failure parsing {}: {}", file.display(), message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PhasiusError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PhasiusError::Htslib { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use crate::extract::Tracks;
+use crate::utils::Reg;
+use rust_htslib::{bgzf, htslib};
+use std::ffi::CString;
+use std::io::Write;
+use std::path::Path;
+
+/// Write the blocks of all regions as a bgzipped and tabix-indexed bed file, with a row per block:
+/// chrom, start, end, sample, phase set and the number of supporting records.
+/// Rows are sorted by contig (in the order of the regions) and position, as tabix requires.
+/// A block found in multiple overlapping regions can be clipped differently in each, so overlapping rows
+/// of the same sample and phase set are merged into one, with the largest support of the merged rows.
+pub fn write_bed(
+    targets: &[Reg],
+    blocks_per_target: &[Tracks],
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut contigs: Vec<&str> = vec![];
+    let mut rows = vec![];
+    for (target, tracks) in targets.iter().zip(blocks_per_target) {
+        let contig = match contigs.iter().position(|c| *c == target.chrom) {
+            Some(index) => index,
+            None => {
+                contigs.push(&target.chrom);
+                contigs.len() - 1
+            }
+        };
+        for block in tracks.iter().flatten().filter(|block| !block.empty) {
+            rows.push((
+                contig,
+                block.start,
+                block.end,
+                block.name.as_str(),
+                block.phaseset,
+                block.support,
+            ));
+        }
+    }
+    rows.sort_unstable_by_key(|&(contig, start, _, sample, phaseset, _)| {
+        (contig, sample, phaseset, start)
+    });
+    let mut merged: Vec<(usize, i64, i64, &str, u32, usize)> = vec![];
+    for row in rows {
+        match merged.last_mut() {
+            Some(last) if (last.0, last.3, last.4) == (row.0, row.3, row.4) && row.1 < last.2 => {
+                last.2 = last.2.max(row.2);
+                last.5 = last.5.max(row.5);
+            }
+            _ => merged.push(row),
+        }
+    }
+    merged.sort_unstable();
+
+    // the writer is dropped, closing the file, before it is indexed
+    {
+        let mut writer = bgzf::Writer::from_path(path)?;
+        writeln!(writer, "#chrom\tstart\tend\tsample\tphaseset\tsupport")?;
+        for (contig, start, end, sample, phaseset, support) in merged {
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}\t{}",
+                contigs[contig], start, end, sample, phaseset, support
+            )?;
+        }
+    }
+    let c_path = CString::new(path.to_string_lossy().as_bytes())?;
+    let ret = unsafe { htslib::tbx_index_build(c_path.as_ptr(), 0, &htslib::tbx_conf_bed) };
+    if ret != 0 {
+        return Err(format!("Unable to build tabix index of {}", path.display()).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::Blocks;
+    use rust_htslib::tbx::{self, Read};
+
+    fn block(name: &str, start: i64, end: i64, phaseset: u32) -> Blocks {
+        Blocks {
+            start,
+            end,
+            name: name.to_string(),
+            phaseset,
+            support: 2,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_write_bed() {
+        let targets = [
+            Reg {
+                chrom: "chr8".to_string(),
+                start: 0,
+                end: 1000,
+            },
+            Reg {
+                chrom: "chr7".to_string(),
+                start: 0,
+                end: 1000,
+            },
+            Reg {
+                chrom: "chr8".to_string(),
+                start: 500,
+                end: 1000,
+            },
+        ];
+        let blocks_per_target = vec![
+            vec![
+                vec![block("a", 600, 900, 600), block("a", 10, 400, 10)],
+                vec![Blocks {
+                    name: "b".to_string(),
+                    empty: true,
+                    ..Default::default()
+                }],
+            ],
+            vec![vec![block("a", 100, 200, 100)]],
+            // the same block, clipped by the region, and a separate block of the same phase set
+            vec![vec![block("a", 500, 950, 600), block("a", 960, 1000, 600)]],
+        ];
+        let path = Path::new("test-data/export-test.bed.gz");
+        write_bed(&targets, &blocks_per_target, path).unwrap();
+
+        let mut reader = tbx::Reader::from_path(path).unwrap();
+        let tid = reader.tid("chr8").unwrap();
+        reader.fetch(tid, 0, 1000).unwrap();
+        let lines: Vec<String> = reader
+            .records()
+            .map(|record| String::from_utf8(record.unwrap()).unwrap())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "chr8\t10\t400\ta\t10\t2",
+                "chr8\t500\t950\ta\t600\t2",
+                "chr8\t960\t1000\ta\t600\t2"
+            ]
+        );
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file("test-data/export-test.bed.gz.tbi").unwrap();
+    }
+}
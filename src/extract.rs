@@ -1,213 +1,878 @@
+use crate::aliases::ContigAliases;
+use crate::blocklist::{self, BlockListFormat};
 use crate::blocks::Blocks;
+use crate::error::PhasiusError;
+use crate::utils::split_explicit_index;
+use crate::vcf::IndexedVcf;
 use bam::ext::BamRecordExtensions;
 use rust_htslib::bam::record::Aux;
+use rust_htslib::bcf::header::{HeaderRecord, TagType};
+use rust_htslib::bcf::record::GenotypeAllele;
+use rust_htslib::bcf::Read as VcfRead;
 use rust_htslib::htslib;
 use rust_htslib::{bam, bam::Read};
-use rust_htslib::{bcf::IndexedReader, bcf::Read as VcfRead};
-use std::path::PathBuf; // for BAM_F*
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::path::{Path, PathBuf}; // for BAM_F*
 
+/// Settings that determine which records are used to build phase blocks
+#[derive(Debug, Clone, Default)]
+pub struct ExtractOptions {
+    /// Number of decompression threads to use per cram/bam
+    pub threads: usize,
+    /// Only create tracks for these samples of a (multi-sample) VCF
+    pub samples: Option<Vec<String>>,
+    /// Infer phase blocks from phased genotypes for VCF records without PS
+    pub phase_from_gt: bool,
+    /// Only use VCF records that pass all filters
+    pub pass_only: bool,
+    /// Only use heterozygous VCF genotypes
+    pub het_only: bool,
+    /// Only use reads with at least this mapping quality
+    pub min_mapq: u8,
+    /// Only use reads with all of these flags set
+    pub include_flags: u16,
+    /// Only use reads with none of these flags set
+    pub exclude_flags: u16,
+    /// Only use reads with at least this aligned length on the reference
+    pub min_aligned_length: i64,
+    /// Reference fasta to decode cram files
+    pub reference: Option<PathBuf>,
+    /// Name tracks after the sample in the file header rather than the file name
+    pub name_from_header: bool,
+    /// Create a track per sample in the read groups of a bam/cram
+    pub split_by_read_group: bool,
+    /// Split phase sets in multiple blocks where their records are more than this apart
+    pub max_gap: Option<i64>,
+    /// Alternative contig names, to find the contig of a region in each file
+    pub aliases: ContigAliases,
+    /// Regions on a contig that is not in a file give empty tracks rather than an error,
+    /// as in the genome-wide mode, in which the contigs come from all inputs together
+    pub allow_missing_contigs: bool,
+}
+
+/// The phase blocks of a file in a region, as one Vec<Blocks> per sample (track) in the file
+pub type Tracks = Vec<Vec<Blocks>>;
+
+/// Get the phase blocks of a file in each of the regions, opening the file only once
 pub fn get_blocks(
     file: &PathBuf,
-    threads: usize,
-    region: &crate::utils::Reg,
-) -> Result<Vec<Blocks>, Box<dyn std::error::Error>> {
+    options: &ExtractOptions,
+    regions: &[crate::utils::Reg],
+) -> Result<Vec<Tracks>, PhasiusError> {
     log::debug!(
-        "get_blocks called for file: {:?}, threads: {}, region: {:?}",
+        "get_blocks called for file: {:?}, options: {:?}, regions: {:?}",
         file,
-        threads,
-        region
+        options,
+        regions
     );
-    let filename = file
-        .clone()
-        .into_os_string()
-        .into_string()
-        .expect("Failed parsing filename");
-    log::debug!("Filename: {}", filename);
-    if file.extension().expect("Failed getting file extension") == "cram"
-        || file.extension().expect("Failed getting file extension") == "bam"
-    {
-        log::debug!("Detected BAM/CRAM file format");
-        blocks_from_bam(file, threads, region)
-    } else if filename.ends_with("vcf") || filename.ends_with("vcf.gz") {
-        log::debug!("Detected VCF file format");
-        blocks_from_vcf(file, region)
-    } else {
-        panic!(
-            "Unsupported file format or file extension not recognized: {}",
-            filename
-        );
+    match detect_format(file)? {
+        InputFormat::Alignment => {
+            log::debug!("Detected BAM/CRAM/SAM file format");
+            blocks_from_bam(file, options, regions)
+        }
+        InputFormat::Variant => {
+            log::debug!("Detected VCF/BCF file format");
+            blocks_from_vcf(file, options, regions)
+        }
+        InputFormat::BlockList(format) => {
+            log::debug!("Detected {:?} block list", format);
+            blocklist::blocks_from_block_list(file, format, options, regions)
+        }
+    }
+}
+
+/// Contig names with their length, if known
+pub type Contigs = Vec<(String, Option<u64>)>;
+
+/// Get the contigs and their lengths from the header of a file, or the listed blocks of a block list.
+/// The length is None if the header does not define it.
+pub fn get_contigs(file: &Path) -> Result<Contigs, PhasiusError> {
+    let (path, _) = split_explicit_index(file);
+    match detect_format(file)? {
+        InputFormat::Alignment => {
+            let bam = bam::Reader::from_path(path).map_err(PhasiusError::htslib(file))?;
+            Ok(bam_contigs(bam.header()))
+        }
+        InputFormat::Variant => {
+            let vcf =
+                rust_htslib::bcf::Reader::from_path(path).map_err(PhasiusError::htslib(file))?;
+            Ok(vcf_contigs(vcf.header()))
+        }
+        InputFormat::BlockList(format) => blocklist::contigs(file, format),
+    }
+}
+
+pub(crate) fn bam_contigs(header: &bam::HeaderView) -> Contigs {
+    (0..header.target_count())
+        .map(|tid| {
+            (
+                String::from_utf8_lossy(header.target_names()[tid as usize]).to_string(),
+                header.target_len(tid),
+            )
+        })
+        .collect()
+}
+
+pub(crate) fn vcf_contigs(header: &rust_htslib::bcf::header::HeaderView) -> Contigs {
+    header
+        .header_records()
+        .into_iter()
+        .filter_map(|record| match record {
+            HeaderRecord::Contig { values, .. } => Some((
+                values.get("ID")?.to_string(),
+                values.get("length").and_then(|l| l.parse().ok()),
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum InputFormat {
+    Alignment,
+    Variant,
+    BlockList(BlockListFormat),
+}
+
+/// Detect the format of a file from its content, rather than its extension
+pub(crate) fn detect_format(file: &Path) -> Result<InputFormat, PhasiusError> {
+    // an explicit index can be appended to the file name as file##idx##index
+    let (path, _) = split_explicit_index(file);
+    let path = CString::new(path.to_string_lossy().as_bytes())
+        .map_err(|_| PhasiusError::Open(file.to_path_buf()))?;
+    let mode = c"r";
+    let mut format: htslib::htsFormat = unsafe { std::mem::zeroed() };
+    let detected = unsafe {
+        let hfile = htslib::hopen(path.as_ptr(), mode.as_ptr());
+        if hfile.is_null() {
+            return Err(PhasiusError::Open(file.to_path_buf()));
+        }
+        let detected = htslib::hts_detect_format2(hfile, path.as_ptr(), &mut format);
+        htslib::hclose(hfile);
+        detected
+    };
+    if detected < 0 {
+        return Err(PhasiusError::UnsupportedFormat(file.to_path_buf()));
+    }
+    match format.format {
+        htslib::htsExactFormat_bam | htslib::htsExactFormat_cram | htslib::htsExactFormat_sam => {
+            Ok(InputFormat::Alignment)
+        }
+        htslib::htsExactFormat_vcf | htslib::htsExactFormat_bcf => Ok(InputFormat::Variant),
+        _ => match blocklist::detect(file).map_err(|_| PhasiusError::Open(file.to_path_buf()))? {
+            Some(format) => Ok(InputFormat::BlockList(format)),
+            None => Err(PhasiusError::UnsupportedFormat(file.to_path_buf())),
+        },
     }
 }
 
-fn construct_blocks<I>(phased_records: I, name: String) -> Option<Vec<Blocks>>
+/// For the name, use the basename and strip the extension. Return the full path if something goes wrong.
+pub(crate) fn file_stem(file: &Path) -> String {
+    const EXTENSIONS: [&str; 14] = [
+        ".cram", ".bam", ".sam.gz", ".sam", ".vcf.gz", ".vcf.bgz", ".vcf", ".bcf", ".tsv.gz",
+        ".tsv", ".txt", ".bed.gz", ".bed", ".blocks",
+    ];
+    file.file_name()
+        .and_then(|f| f.to_str())
+        .map(|s| {
+            EXTENSIONS
+                .iter()
+                .find(|ext| s.to_lowercase().ends_with(*ext))
+                .map_or(s, |ext| &s[..s.len() - ext.len()])
+                .to_string()
+        })
+        .unwrap_or_else(|| file.display().to_string())
+}
+
+/// Construct the phase blocks from (start, end, phaseset) records, sorted by start position.
+/// With max_gap, a phase set is split in multiple blocks where its records are more than max_gap apart.
+pub(crate) fn construct_blocks<I>(
+    phased_records: I,
+    name: String,
+    max_gap: Option<i64>,
+) -> Option<Vec<Blocks>>
 where
     I: Iterator<Item = (i64, i64, u32)>,
 {
-    // first, sort the phased records by phaseset, then by start position
-    let mut phased_records: Vec<_> = phased_records.collect();
-    phased_records.sort_by_key(|&(start, _, phaseset)| (phaseset, start));
-    let mut phased_records = phased_records.into_iter();
-    let mut phaseblocks = vec![];
-    let (mut start1, mut block_end, mut phaseset1) = phased_records.next()?;
-
+    let mut builder = BlockBuilder::new(max_gap);
     for (start, end, phaseset) in phased_records {
-        if phaseset == phaseset1 {
-            block_end = end;
-        } else {
-            phaseblocks.push(Blocks {
-                start: start1,
-                end: block_end,
+        builder.add(start, end, phaseset);
+    }
+    builder.finish(name)
+}
+
+/// The extent and support of a block that is still being built
+struct OpenBlock {
+    start: i64,
+    end: i64,
+    support: usize,
+    last_record: i64,
+}
+
+/// Builds phase blocks from records coming in order of start position (as fetched from an indexed file),
+/// keeping only the open block of each phase set rather than all records,
+/// so that memory does not grow with the size of the region or the depth.
+pub(crate) struct BlockBuilder {
+    max_gap: Option<i64>,
+    open: HashMap<u32, OpenBlock>,
+    /// blocks closed at a gap larger than max_gap, with their phase set
+    closed: Vec<(u32, OpenBlock)>,
+}
+
+impl BlockBuilder {
+    pub(crate) fn new(max_gap: Option<i64>) -> BlockBuilder {
+        BlockBuilder {
+            max_gap,
+            open: HashMap::new(),
+            closed: vec![],
+        }
+    }
+
+    pub(crate) fn add(&mut self, start: i64, end: i64, phaseset: u32) {
+        let max_gap = self.max_gap;
+        match self.open.get_mut(&phaseset) {
+            Some(block) if max_gap.is_none_or(|max_gap| start - block.end <= max_gap) => {
+                // an earlier (longer) record can extend further than the last one
+                block.end = block.end.max(end);
+                block.support += 1;
+                block.last_record = start;
+            }
+            _ => {
+                let block = OpenBlock {
+                    start,
+                    end,
+                    support: 1,
+                    last_record: start,
+                };
+                if let Some(previous) = self.open.insert(phaseset, block) {
+                    self.closed.push((phaseset, previous));
+                }
+            }
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.open.is_empty()
+    }
+
+    /// The blocks of the track named name, sorted by start position, or None without records
+    pub(crate) fn finish(self, name: String) -> Option<Vec<Blocks>> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut phaseblocks: Vec<Blocks> = self
+            .closed
+            .into_iter()
+            .chain(self.open)
+            .map(|(phaseset, block)| Blocks {
+                start: block.start,
+                end: block.end,
                 name: name.clone(),
                 empty: false,
-            });
-            start1 = start;
-            block_end = end;
-            phaseset1 = phaseset;
-        }
+                phaseset,
+                support: block.support,
+                first_record: block.start,
+                last_record: block.last_record,
+                ..Default::default()
+            })
+            .collect();
+        phaseblocks.sort_by_key(|block| (block.start, block.phaseset));
+        mark_overlapping(&mut phaseblocks);
+        Some(phaseblocks)
     }
-    phaseblocks.push(Blocks {
-        start: start1,
-        end: block_end,
-        name,
-        empty: false,
-    });
+}
+
+/// A track without blocks for each of the names
+fn empty_tracks(names: &[String]) -> Tracks {
+    names
+        .iter()
+        .map(|name| {
+            vec![Blocks {
+                start: 0,
+                end: 0,
+                name: name.clone(),
+                empty: true,
+                ..Default::default()
+            }]
+        })
+        .collect()
+}
 
-    Some(phaseblocks)
+/// Mark blocks that overlap with another block, which happens for interleaved phase sets.
+/// Overlap is decided on the supporting records rather than the extent of the block,
+/// as the last reads of a phase set reach into the next one without the phase sets being interleaved.
+/// The blocks have to be sorted by start position, which is the position of their first record.
+fn mark_overlapping(blocks: &mut [Blocks]) {
+    for i in 0..blocks.len() {
+        for j in i + 1..blocks.len() {
+            if blocks[j].first_record >= blocks[i].last_record {
+                break;
+            }
+            blocks[i].overlapping = true;
+            blocks[j].overlapping = true;
+        }
+    }
 }
 
 fn blocks_from_bam(
-    bamp: &PathBuf,
-    threads: usize,
+    bamp: &Path,
+    options: &ExtractOptions,
+    regions: &[crate::utils::Reg],
+) -> Result<Vec<Tracks>, PhasiusError> {
+    let mut bam = open_indexed_bam(bamp)?;
+    if let Some(reference) = &options.reference {
+        bam.set_reference(reference)
+            .map_err(PhasiusError::htslib(bamp))?;
+    }
+    decode_required_fields(&mut bam).map_err(PhasiusError::htslib(bamp))?;
+    bam.set_threads(options.threads)
+        .map_err(PhasiusError::htslib(bamp))?;
+
+    let name = file_stem(bamp);
+    let name = if options.name_from_header {
+        sample_from_read_groups(bam.header(), bamp).unwrap_or(name)
+    } else {
+        name
+    };
+    // when splitting by read group, reads of the same sample go to the same track,
+    // and reads without (known) read group remain in the track named after the file
+    let mut names = vec![name];
+    let mut track_of_read_group: HashMap<String, usize> = HashMap::new();
+    if options.split_by_read_group {
+        for (id, sample) in read_groups(bam.header()) {
+            let track = match names.iter().skip(1).position(|n| *n == sample) {
+                Some(index) => index + 1,
+                None => {
+                    names.push(sample);
+                    names.len() - 1
+                }
+            };
+            track_of_read_group.insert(id, track);
+        }
+    }
+
+    let mut removed = RemovedReads::default();
+    let mut blocks_per_region = vec![];
+    for region in regions {
+        let tid = options
+            .aliases
+            .resolve(&region.chrom, |c| bam.header().tid(c.as_bytes()).is_some())
+            .and_then(|chrom| bam.header().tid(chrom.as_bytes()));
+        let Some(tid) = tid else {
+            if options.allow_missing_contigs {
+                log::debug!("Contig {} not in {}", region.chrom, bamp.display());
+                // after splitting by read group, the file track is only kept if it has reads
+                let split = usize::from(names.len() > 1);
+                blocks_per_region.push(empty_tracks(&names[split..]));
+                continue;
+            }
+            return Err(PhasiusError::ContigNotFound {
+                contig: region.chrom.clone(),
+                file: bamp.to_path_buf(),
+            });
+        };
+        bam.fetch((tid, region.start, region.end))
+            .map_err(PhasiusError::htslib(bamp))?;
+
+        let mut builders: Vec<BlockBuilder> = (0..names.len())
+            .map(|_| BlockBuilder::new(options.max_gap))
+            .collect();
+        for read in bam.rc_records() {
+            let read = read.map_err(PhasiusError::htslib(bamp))?;
+            if let Some(filter) = read_filter(&read, options) {
+                removed.add(filter);
+                continue;
+            }
+            let phaseset = get_phaseset(&read).map_err(|found| PhasiusError::BadPsType {
+                file: bamp.to_path_buf(),
+                found,
+            })?;
+            if let Some(phaseset) = phaseset {
+                let track = match read.aux(b"RG") {
+                    Ok(Aux::String(read_group)) => track_of_read_group.get(read_group).copied(),
+                    _ => None,
+                };
+                builders[track.unwrap_or(0)].add(read.pos(), read.reference_end(), phaseset);
+            }
+        }
+        blocks_per_region.push(bam_tracks(builders, &names, bamp, region));
+    }
+    if removed.total() > 0 {
+        eprintln!(
+            "Removed {} reads from BAM file {}: {}",
+            removed.total(),
+            bamp.display(),
+            removed
+        );
+    }
+    Ok(blocks_per_region)
+}
+
+/// Finish the blocks of each track from the phased reads in a region
+fn bam_tracks(
+    builders: Vec<BlockBuilder>,
+    names: &[String],
+    bamp: &Path,
     region: &crate::utils::Reg,
-) -> Result<Vec<Blocks>, Box<dyn std::error::Error>> {
-    let mut bam = bam::IndexedReader::from_path(bamp)?;
+) -> Tracks {
+    let split = names.len() > 1;
+    builders
+        .into_iter()
+        .zip(names.iter().cloned())
+        .enumerate()
+        // after splitting, the file track is only kept if it has reads without read group
+        .filter(|(track, (builder, _))| !(split && *track == 0 && builder.is_empty()))
+        .map(|(_, (builder, name))| match builder.finish(name.clone()) {
+            Some(blocks) => blocks,
+            None => {
+                if split {
+                    eprintln!(
+                        "Warning: No phased records found for {} in {} of BAM file {}",
+                        name,
+                        region,
+                        bamp.display()
+                    );
+                } else {
+                    eprintln!(
+                        "Warning: No phased records found in {} of BAM file {}",
+                        region,
+                        bamp.display()
+                    );
+                }
+                vec![Blocks {
+                    start: 0,
+                    end: 0,
+                    name,
+                    empty: true,
+                    ..Default::default()
+                }]
+            }
+        })
+        .collect()
+}
 
-    let tid = bam
-        .header()
-        .tid(region.chrom.as_bytes())
-        .ok_or("chromosome not found")?;
-    bam.fetch((tid, region.start, region.end))?;
-    bam.set_threads(threads)?;
-
-    // For the name, use the basename and strip the extension. Return the full path if something goes wrong.
-    let name = bamp
-        .file_name()
-        .and_then(|f| f.to_str())
-        .map(|s| {
-            let stem = if let Some(stem) = s.strip_suffix(".cram") {
-                stem
-            } else if let Some(stem) = s.strip_suffix(".bam") {
-                stem
-            } else {
-                s
+/// Open an indexed bam/cram, with an explicit index if given as file##idx##index
+pub(crate) fn open_indexed_bam(path: &Path) -> Result<bam::IndexedReader, PhasiusError> {
+    let reader = match split_explicit_index(path) {
+        (file, Some(index)) => bam::IndexedReader::from_path_and_index(file, index),
+        (_, None) => bam::IndexedReader::from_path(path),
+    };
+    reader.map_err(|e| match e {
+        rust_htslib::errors::Error::BamInvalidIndex { .. } => {
+            PhasiusError::MissingIndex(path.to_path_buf())
+        }
+        // the file itself exists, as its format was detected
+        rust_htslib::errors::Error::FileNotFound { .. } => {
+            PhasiusError::MissingIndex(path.to_path_buf())
+        }
+        e => PhasiusError::htslib(path)(e),
+    })
+}
+
+/// Get the sample name from the SM tag of the read groups, warning if there is none or more than one
+fn sample_from_read_groups(header: &bam::HeaderView, bamp: &Path) -> Option<String> {
+    let mut samples: Vec<String> = vec![];
+    for (_, sample) in read_groups(header) {
+        if !samples.contains(&sample) {
+            samples.push(sample);
+        }
+    }
+    match samples.len() {
+        0 => eprintln!(
+            "Warning: No read group with SM tag in BAM file {}, using the file name",
+            bamp.display()
+        ),
+        1 => (),
+        _ => eprintln!(
+            "Warning: Multiple samples in the read groups of BAM file {} ({}), using {}",
+            bamp.display(),
+            samples.join(", "),
+            samples[0]
+        ),
+    }
+    samples.into_iter().next()
+}
+
+/// Get the ID and SM tag of all read groups in the header
+fn read_groups(header: &bam::HeaderView) -> Vec<(String, String)> {
+    String::from_utf8_lossy(header.as_bytes())
+        .lines()
+        .filter(|line| line.starts_with("@RG\t"))
+        .filter_map(|line| {
+            let tag = |tag: &str| {
+                line.split('\t')
+                    .find_map(|field| field.strip_prefix(tag))
+                    .map(str::to_string)
             };
-            stem.to_string()
+            Some((tag("ID:")?, tag("SM:")?))
         })
-        .unwrap_or_else(|| bamp.display().to_string());
-    let phased_reads_iter = bam
-        .rc_records()
-        .map(|r| r.expect("Failure parsing Bam file"))
-        .filter(|read| read.flags() & (htslib::BAM_FUNMAP | htslib::BAM_FSECONDARY) as u16 == 0)
-        .map(|read| (read.pos(), read.reference_end(), get_phaseset(&read)))
-        .filter(|(_, _, p)| p.is_some())
-        .map(|(start, end, p)| (start, end, p.unwrap()));
-
-    match construct_blocks(phased_reads_iter, name.clone()) {
-        Some(blocks) => Ok(blocks),
-        None => {
-            eprintln!(
-                "Warning: No phased records found in BAM file {}",
-                bamp.display()
-            );
-            Ok(vec![Blocks {
-                start: 0,
-                end: 0,
-                name,
-                empty: true,
-            }])
+        .collect()
+}
+
+/// Only decode the cram fields required for phase blocks, skipping e.g. sequence and base qualities.
+/// This does not require the reference sequence, and is a no-op for bam files.
+fn decode_required_fields(bam: &mut bam::IndexedReader) -> Result<(), rust_htslib::errors::Error> {
+    let required_fields = htslib::sam_fields_SAM_RNAME
+        | htslib::sam_fields_SAM_POS
+        | htslib::sam_fields_SAM_FLAG
+        | htslib::sam_fields_SAM_MAPQ
+        | htslib::sam_fields_SAM_CIGAR
+        | htslib::sam_fields_SAM_AUX;
+    bam.set_cram_options(
+        htslib::hts_fmt_option_CRAM_OPT_REQUIRED_FIELDS,
+        required_fields,
+    )?;
+    // MD and NM tags would have to be generated from the reference
+    bam.set_cram_options(htslib::hts_fmt_option_CRAM_OPT_DECODE_MD, 0)?;
+    Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+enum ReadFilter {
+    Flag,
+    MapQ,
+    Length,
+}
+
+/// Number of reads removed from a bam/cram, per filter
+#[derive(Debug, Default)]
+struct RemovedReads {
+    flag: usize,
+    mapq: usize,
+    length: usize,
+}
+
+impl RemovedReads {
+    fn add(&mut self, filter: ReadFilter) {
+        match filter {
+            ReadFilter::Flag => self.flag += 1,
+            ReadFilter::MapQ => self.mapq += 1,
+            ReadFilter::Length => self.length += 1,
         }
     }
+
+    fn total(&self) -> usize {
+        self.flag + self.mapq + self.length
+    }
+}
+
+impl std::fmt::Display for RemovedReads {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} by flag, {} by mapping quality, {} by aligned length",
+            self.flag, self.mapq, self.length
+        )
+    }
 }
 
-fn get_phaseset(record: &bam::Record) -> Option<u32> {
+/// Check the read against the filters, returning the first one that removes it.
+/// Unmapped reads are always removed, as they have no alignment to contribute to a block.
+fn read_filter(read: &bam::Record, options: &ExtractOptions) -> Option<ReadFilter> {
+    let flags = read.flags();
+    if flags & (options.exclude_flags | htslib::BAM_FUNMAP as u16) != 0
+        || flags & options.include_flags != options.include_flags
+    {
+        Some(ReadFilter::Flag)
+    } else if read.mapq() < options.min_mapq {
+        Some(ReadFilter::MapQ)
+    } else if read.reference_end() - read.pos() < options.min_aligned_length {
+        Some(ReadFilter::Length)
+    } else {
+        None
+    }
+}
+
+/// Get the PS tag of a read, or the type of the tag if it is not an integer
+fn get_phaseset(record: &bam::Record) -> Result<Option<u32>, String> {
     match record.aux(b"PS") {
         Ok(value) => match value {
-            Aux::U8(v) => Some(u32::from(v)),
-            Aux::U16(v) => Some(u32::from(v)),
-            Aux::U32(v) => Some(v),
-            Aux::I32(v) => Some(v as u32),
-            _ => panic!("Unexpected type of Aux {:?}", value),
+            Aux::U8(v) => Ok(Some(u32::from(v))),
+            Aux::U16(v) => Ok(Some(u32::from(v))),
+            Aux::U32(v) => Ok(Some(v)),
+            Aux::I8(v) => Ok(Some(v as u32)),
+            Aux::I16(v) => Ok(Some(v as u32)),
+            Aux::I32(v) => Ok(Some(v as u32)),
+            _ => Err(format!("{:?}", value)),
         },
-        Err(_e) => None,
+        Err(_e) => Ok(None),
     }
 }
 
 fn blocks_from_vcf(
-    vcff: &PathBuf,
-    region: &crate::utils::Reg,
-) -> Result<Vec<Blocks>, Box<dyn std::error::Error>> {
-    let mut vcf = IndexedReader::from_path(vcff)?;
-    let rid = vcf
-        .header()
-        .name2rid(region.chrom.as_bytes())
-        .expect("Failed getting rid");
+    vcff: &Path,
+    options: &ExtractOptions,
+    regions: &[crate::utils::Reg],
+) -> Result<Vec<Tracks>, PhasiusError> {
+    let mut vcf = IndexedVcf::from_path(vcff)?;
+    let name = file_stem(vcff);
+    // PS is read as an integer, as the PS tag of reads in a bam
+    if let Ok((tag_type, _)) = vcf.header().format_type(b"PS") {
+        if !matches!(tag_type, TagType::Integer) {
+            return Err(PhasiusError::BadPsType {
+                file: vcff.to_path_buf(),
+                found: format!("{:?}", tag_type),
+            });
+        }
+    }
 
-    // For the name, use the basename and strip the extension. Return the full path if something goes wrong.
-    let name = vcff
-        .file_name()
-        .and_then(|f| f.to_str())
-        .map(|s| {
-            let stem = if let Some(stem) = s.strip_suffix(".vcf") {
-                stem
-            } else if let Some(stem) = s.strip_suffix(".vcf.gz") {
-                stem
-            } else {
-                s
-            };
-            stem.to_string()
-        })
-        .unwrap_or_else(|| vcff.display().to_string());
+    let header_samples: Vec<String> = vcf
+        .header()
+        .samples()
+        .iter()
+        .map(|s| String::from_utf8_lossy(s).to_string())
+        .collect();
+    if header_samples.is_empty() {
+        eprintln!("Warning: No samples found in VCF file {}", vcff.display());
+        return Ok(regions
+            .iter()
+            .map(|_| {
+                vec![vec![Blocks {
+                    start: 0,
+                    end: 0,
+                    name: name.clone(),
+                    empty: true,
+                    ..Default::default()
+                }]]
+            })
+            .collect());
+    }
+    let selected = select_samples(&header_samples, &options.samples, vcff);
+    if selected.is_empty() {
+        eprintln!(
+            "Warning: None of the requested samples found in VCF file {}",
+            vcff.display()
+        );
+        return Ok(vec![vec![]; regions.len()]);
+    }
+    // a single-sample VCF keeps the file name unless the name should come from the header,
+    // the tracks of a multi-sample VCF are named after their sample
+    let names: Vec<String> = if header_samples.len() == 1 && !options.name_from_header {
+        vec![name]
+    } else {
+        selected
+            .iter()
+            .map(|&i| header_samples[i].clone())
+            .collect()
+    };
 
-    vcf.fetch(rid, region.start as u64, Some(region.end as u64))
-        .expect("Failed fetching region from VCF");
+    let mut ignored = vec![IgnoredRecords::default(); selected.len()];
+    let mut blocks_per_region = vec![];
+    for region in regions {
+        let rid = options
+            .aliases
+            .resolve(&region.chrom, |c| {
+                vcf.header().name2rid(c.as_bytes()).is_ok()
+            })
+            .and_then(|chrom| vcf.header().name2rid(chrom.as_bytes()).ok());
+        let Some(rid) = rid else {
+            if options.allow_missing_contigs {
+                log::debug!("Contig {} not in {}", region.chrom, vcff.display());
+                blocks_per_region.push(empty_tracks(&names));
+                continue;
+            }
+            return Err(PhasiusError::ContigNotFound {
+                contig: region.chrom.clone(),
+                file: vcff.to_path_buf(),
+            });
+        };
+        vcf.fetch(rid, region.start as u64, region.end as u64)?;
 
-    let phased_variants = vcf
-        .records()
-        .map(|record| record.expect("Failed getting record from VCF"))
-        .map(|record| (record.pos(), record.end(), record.format(b"PS").integer()))
-        .filter(|(_, _, record)| record.is_ok())
-        .map(|(start, end, record)| {
-            (
-                start,
-                end,
-                Some(
-                    *record
-                        .expect("Failed getting phaseset from VCF")
-                        .first()
-                        .expect("Failed getting phaseset from VCF")
-                        .first()
-                        .expect("Failed getting phaseset from VCF") as u32,
-                ),
-            )
-        })
-        .filter(|(_, _, phaseset)| phaseset.is_some())
-        .map(|(start, end, phaseset)| (start, end, phaseset.unwrap()));
+        let mut builders: Vec<BlockBuilder> = (0..selected.len())
+            .map(|_| BlockBuilder::new(options.max_gap))
+            .collect();
+        // the phase set inferred from genotypes without PS, per track
+        let mut inferred_phasesets: Vec<Option<u32>> = vec![None; selected.len()];
+        while let Some(record) = vcf.next_record() {
+            let record = record?;
+            let passes_filter = !options.pass_only || record.pass;
+            for (track, &sample) in selected.iter().enumerate() {
+                let genotype = record.genotype(sample);
+                let phaseset =
+                    record
+                        .phaseset(sample)
+                        .map(|p| p as u32)
+                        .or_else(|| match &genotype {
+                            Some(genotype) if options.phase_from_gt && passes_filter => {
+                                infer_phaseset(&mut inferred_phasesets[track], genotype, record.pos)
+                            }
+                            _ => None,
+                        });
+                // records without phase set do not contribute to blocks, and are not counted as ignored
+                let Some(phaseset) = phaseset else {
+                    continue;
+                };
+                let reason = if !passes_filter {
+                    Some(IgnoreReason::Filtered)
+                } else {
+                    genotype
+                        .as_ref()
+                        .and_then(|g| genotype_ignore_reason(g, options.het_only))
+                };
+                match reason {
+                    Some(reason) => ignored[track].add(reason),
+                    None => builders[track].add(record.pos, record.end, phaseset),
+                }
+            }
+        }
 
-    match construct_blocks(phased_variants, name.clone()) {
-        Some(blocks) => Ok(blocks),
-        None => {
+        blocks_per_region.push(
+            builders
+                .into_iter()
+                .zip(names.iter().cloned())
+                .map(|(builder, name)| match builder.finish(name.clone()) {
+                    Some(blocks) => blocks,
+                    None => {
+                        eprintln!(
+                            "Warning: No phased records found for {} in {} of VCF file {}",
+                            name,
+                            region,
+                            vcff.display()
+                        );
+                        vec![Blocks {
+                            start: 0,
+                            end: 0,
+                            name,
+                            empty: true,
+                            ..Default::default()
+                        }]
+                    }
+                })
+                .collect(),
+        );
+    }
+    for (name, ignored) in names.iter().zip(ignored) {
+        if ignored.total() > 0 {
             eprintln!(
-                "Warning: No phased records found in VCF file {}",
-                vcff.display()
-            );
-            Ok(vec![Blocks {
-                start: 0,
-                end: 0,
+                "Ignored {} records for {} in VCF file {}: {}",
+                ignored.total(),
                 name,
-                empty: true,
-            }])
+                vcff.display(),
+                ignored
+            );
+        }
+    }
+    Ok(blocks_per_region)
+}
+
+#[derive(Debug, PartialEq)]
+enum IgnoreReason {
+    Filtered,
+    Missing,
+    Unphased,
+    Homozygous,
+}
+
+/// Number of VCF records with a phase set that were not used for a sample, per reason
+#[derive(Debug, Clone, Default)]
+struct IgnoredRecords {
+    filtered: usize,
+    missing: usize,
+    unphased: usize,
+    homozygous: usize,
+}
+
+impl IgnoredRecords {
+    fn add(&mut self, reason: IgnoreReason) {
+        match reason {
+            IgnoreReason::Filtered => self.filtered += 1,
+            IgnoreReason::Missing => self.missing += 1,
+            IgnoreReason::Unphased => self.unphased += 1,
+            IgnoreReason::Homozygous => self.homozygous += 1,
         }
     }
+
+    fn total(&self) -> usize {
+        self.filtered + self.missing + self.unphased + self.homozygous
+    }
+}
+
+impl std::fmt::Display for IgnoredRecords {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} filtered, {} missing genotype, {} unphased, {} homozygous",
+            self.filtered, self.missing, self.unphased, self.homozygous
+        )
+    }
+}
+
+/// Check if a genotype can support a phase block: it has to be called and phased,
+/// and heterozygous if requested
+fn genotype_ignore_reason(genotype: &[GenotypeAllele], het_only: bool) -> Option<IgnoreReason> {
+    if called_alleles(genotype).is_empty() {
+        Some(IgnoreReason::Missing)
+    } else if !is_phased(genotype) {
+        Some(IgnoreReason::Unphased)
+    } else if het_only && !is_heterozygous(genotype) {
+        Some(IgnoreReason::Homozygous)
+    } else {
+        None
+    }
+}
+
+/// Infer the phase set of a genotype without PS tag. Consecutive phased genotypes form a single block,
+/// identified by the 1-based position of its first variant (like PS), and unphased heterozygous genotypes break it.
+fn infer_phaseset(current: &mut Option<u32>, genotype: &[GenotypeAllele], pos: i64) -> Option<u32> {
+    if is_phased(genotype) {
+        Some(*current.get_or_insert(pos as u32 + 1))
+    } else {
+        if is_heterozygous(genotype) {
+            *current = None;
+        }
+        None
+    }
+}
+
+/// The alleles of a genotype, without the padding htslib adds to genotypes of a lower ploidy
+fn called_alleles(genotype: &[GenotypeAllele]) -> Vec<GenotypeAllele> {
+    genotype
+        .iter()
+        .copied()
+        .filter(|a| a.index().is_some())
+        .filter(|a| !matches!(a, GenotypeAllele::Phased(i) | GenotypeAllele::Unphased(i) if *i < 0))
+        .collect()
+}
+
+/// htslib always marks the first allele as unphased, so a genotype is phased if all other alleles are
+fn is_phased(genotype: &[GenotypeAllele]) -> bool {
+    let alleles = called_alleles(genotype);
+    alleles.len() > 1
+        && alleles[1..]
+            .iter()
+            .all(|a| matches!(a, GenotypeAllele::Phased(_)))
+}
+
+fn is_heterozygous(genotype: &[GenotypeAllele]) -> bool {
+    let alleles = called_alleles(genotype);
+    alleles.iter().any(|a| a.index() != alleles[0].index())
+}
+
+/// Get the indices of the requested samples in the VCF header, or of all samples if none were requested.
+/// Requested samples that are not in the header are skipped with a warning.
+fn select_samples(
+    header_samples: &[String],
+    requested: &Option<Vec<String>>,
+    vcff: &Path,
+) -> Vec<usize> {
+    match requested {
+        Some(requested) => requested
+            .iter()
+            .filter_map(|sample| {
+                let index = header_samples.iter().position(|s| s == sample);
+                if index.is_none() {
+                    eprintln!(
+                        "Warning: Sample {} not found in VCF file {}",
+                        sample,
+                        vcff.display()
+                    );
+                }
+                index
+            })
+            .collect(),
+        None => (0..header_samples.len()).collect(),
+    }
 }
 
 #[cfg(test)]
@@ -217,7 +882,8 @@ mod tests {
     #[test]
     fn test_construct_blocks() {
         let phased_records = vec![(1, 2, 1), (3, 4, 1), (5, 6, 2), (7, 8, 2)];
-        let blocks = construct_blocks(phased_records.into_iter(), "test".to_string()).unwrap();
+        let blocks =
+            construct_blocks(phased_records.into_iter(), "test".to_string(), None).unwrap();
         assert_eq!(blocks.len(), 2);
         assert_eq!(blocks[0].start, 1);
         assert_eq!(blocks[0].end, 4);
@@ -226,13 +892,449 @@ mod tests {
     }
 
     #[test]
-    fn test_extension() {
-        let path = PathBuf::from("test.vcf.gz");
-        let filename = path
-            .clone()
-            .into_os_string()
-            .into_string()
-            .expect("Failed parsing filename");
-        assert!(filename.ends_with("vcf.gz"));
+    fn test_construct_blocks_extent() {
+        // the first record of phase set 1 extends beyond the last one
+        let phased_records = vec![(1, 20, 1), (3, 4, 1), (25, 30, 2)];
+        let blocks =
+            construct_blocks(phased_records.into_iter(), "test".to_string(), None).unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].end, 20);
+        assert_eq!(blocks[0].support, 2);
+        assert_eq!((blocks[0].first_record, blocks[0].last_record), (1, 3));
+        assert!(!blocks[0].overlapping);
+        assert!(!blocks[1].overlapping);
+    }
+
+    #[test]
+    fn test_construct_blocks_interleaved() {
+        let phased_records = vec![(1, 5, 1), (4, 8, 2), (7, 12, 1), (11, 15, 2), (20, 25, 3)];
+        let blocks =
+            construct_blocks(phased_records.into_iter(), "test".to_string(), None).unwrap();
+        assert_eq!(blocks.len(), 3);
+        assert_eq!((blocks[0].start, blocks[0].end), (1, 12));
+        assert_eq!((blocks[1].start, blocks[1].end), (4, 15));
+        assert!(blocks[0].overlapping);
+        assert!(blocks[1].overlapping);
+        assert!(!blocks[2].overlapping);
+    }
+
+    #[test]
+    fn test_construct_blocks_read_tails() {
+        // long reads of phase set 1 reach into phase set 2, but the reads of both do not alternate
+        let phased_records = (0..10)
+            .map(|i| (i * 100, i * 100 + 1000, 1))
+            .chain((10..20).map(|i| (i * 100, i * 100 + 1000, 2)));
+        let blocks = construct_blocks(phased_records, "test".to_string(), None).unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!((blocks[0].end, blocks[1].start), (1900, 1000));
+        assert!(!blocks[0].overlapping);
+        assert!(!blocks[1].overlapping);
+    }
+
+    #[test]
+    fn test_construct_blocks_max_gap() {
+        let phased_records = vec![(1, 10, 1), (5, 20, 1), (100, 120, 1), (125, 130, 1)];
+        let blocks =
+            construct_blocks(phased_records.clone().into_iter(), "test".to_string(), None).unwrap();
+        assert_eq!(blocks.len(), 1);
+        let blocks =
+            construct_blocks(phased_records.into_iter(), "test".to_string(), Some(50)).unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!((blocks[0].start, blocks[0].end), (1, 20));
+        assert_eq!((blocks[1].start, blocks[1].end), (100, 130));
+        assert_eq!(blocks[0].phaseset, 1);
+        assert_eq!(blocks[1].phaseset, 1);
+        assert_eq!(blocks[1].support, 2);
+        assert_eq!((blocks[1].first_record, blocks[1].last_record), (100, 125));
+    }
+
+    #[test]
+    fn test_block_builder() {
+        // only the open block of each phase set is kept while records stream in
+        let mut builder = BlockBuilder::new(Some(50));
+        for (start, end, phaseset) in [(1, 10, 1), (5, 20, 2), (8, 30, 1), (100, 120, 1)] {
+            builder.add(start, end, phaseset);
+        }
+        assert_eq!(builder.open.len(), 2);
+        assert_eq!(builder.closed.len(), 1);
+        let blocks = builder.finish("test".to_string()).unwrap();
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(
+            (blocks[0].start, blocks[0].end, blocks[0].phaseset),
+            (1, 30, 1)
+        );
+        assert_eq!(
+            (blocks[1].start, blocks[1].end, blocks[1].phaseset),
+            (5, 20, 2)
+        );
+        assert_eq!(
+            (blocks[2].start, blocks[2].end, blocks[2].phaseset),
+            (100, 120, 1)
+        );
+        assert!(blocks[0].overlapping && blocks[1].overlapping);
+        assert!(BlockBuilder::new(None).finish("test".to_string()).is_none());
+    }
+
+    #[test]
+    fn test_select_samples() {
+        let header_samples = vec!["A".to_string(), "B".to_string(), "C".to_string()];
+        let vcf = Path::new("x.vcf.gz");
+        assert_eq!(select_samples(&header_samples, &None, vcf), vec![0, 1, 2]);
+        let requested = Some(vec!["C".to_string(), "D".to_string(), "A".to_string()]);
+        assert_eq!(select_samples(&header_samples, &requested, vcf), vec![2, 0]);
+    }
+
+    #[test]
+    fn test_genotype_phasing() {
+        use GenotypeAllele::*;
+        assert!(is_phased(&[Unphased(0), Phased(1)]));
+        assert!(!is_phased(&[Unphased(0), Unphased(1)]));
+        assert!(!is_phased(&[UnphasedMissing, PhasedMissing]));
+        assert!(is_heterozygous(&[Unphased(0), Unphased(1)]));
+        assert!(!is_heterozygous(&[Unphased(1), Phased(1)]));
+        assert!(!is_heterozygous(&[Unphased(1)]));
+    }
+
+    #[test]
+    fn test_infer_phaseset() {
+        use GenotypeAllele::*;
+        let mut current = None;
+        assert_eq!(
+            infer_phaseset(&mut current, &[Unphased(0), Phased(1)], 99),
+            Some(100)
+        );
+        assert_eq!(
+            infer_phaseset(&mut current, &[Unphased(1), Phased(1)], 199),
+            Some(100)
+        );
+        // unphased homozygous genotypes do not break the block
+        assert_eq!(
+            infer_phaseset(&mut current, &[Unphased(1), Unphased(1)], 299),
+            None
+        );
+        assert_eq!(
+            infer_phaseset(&mut current, &[Unphased(1), Phased(0)], 399),
+            Some(100)
+        );
+        // unphased heterozygous genotypes do
+        assert_eq!(
+            infer_phaseset(&mut current, &[Unphased(0), Unphased(1)], 499),
+            None
+        );
+        assert_eq!(
+            infer_phaseset(&mut current, &[Unphased(0), Phased(1)], 599),
+            Some(600)
+        );
+    }
+
+    #[test]
+    fn test_genotype_ignore_reason() {
+        use GenotypeAllele::*;
+        assert_eq!(
+            genotype_ignore_reason(&[Unphased(0), Phased(1)], true),
+            None
+        );
+        assert_eq!(
+            genotype_ignore_reason(&[Unphased(1), Phased(1)], false),
+            None
+        );
+        assert_eq!(
+            genotype_ignore_reason(&[Unphased(1), Phased(1)], true),
+            Some(IgnoreReason::Homozygous)
+        );
+        assert_eq!(
+            genotype_ignore_reason(&[Unphased(0), Unphased(1)], false),
+            Some(IgnoreReason::Unphased)
+        );
+        assert_eq!(
+            genotype_ignore_reason(&[UnphasedMissing, PhasedMissing], false),
+            Some(IgnoreReason::Missing)
+        );
+    }
+
+    #[test]
+    fn test_read_filter() {
+        use rust_htslib::bam::record::{Cigar, CigarString};
+        let options = ExtractOptions {
+            min_mapq: 5,
+            exclude_flags: (htslib::BAM_FSECONDARY | htslib::BAM_FSUPPLEMENTARY) as u16,
+            min_aligned_length: 50,
+            ..Default::default()
+        };
+        let mut read = bam::Record::new();
+        read.set(
+            b"read",
+            Some(&CigarString(vec![Cigar::Match(100)])),
+            &[b'A'; 100],
+            &[30; 100],
+        );
+        read.set_flags(0);
+        read.set_tid(0);
+        read.set_pos(1000);
+        read.set_mapq(60);
+        assert_eq!(read_filter(&read, &options), None);
+        read.set_flags(htslib::BAM_FSUPPLEMENTARY as u16);
+        assert_eq!(read_filter(&read, &options), Some(ReadFilter::Flag));
+        read.set_flags(0);
+        read.set_mapq(0);
+        assert_eq!(read_filter(&read, &options), Some(ReadFilter::MapQ));
+        read.set_mapq(60);
+        read.set(
+            b"read",
+            Some(&CigarString(vec![Cigar::Match(40)])),
+            &[b'A'; 40],
+            &[30; 40],
+        );
+        assert_eq!(read_filter(&read, &options), Some(ReadFilter::Length));
+        let options = ExtractOptions {
+            include_flags: htslib::BAM_FPAIRED as u16,
+            ..Default::default()
+        };
+        assert_eq!(read_filter(&read, &options), Some(ReadFilter::Flag));
+    }
+
+    #[test]
+    fn test_read_groups() {
+        let header = bam::HeaderView::from_bytes(
+            b"@SQ\tSN:chr1\tLN:1000\n@RG\tID:rg1\tSM:S1\n@RG\tID:rg2\tPL:ONT\tSM:S2\n@RG\tID:rg3\n",
+        );
+        assert_eq!(
+            read_groups(&header),
+            vec![
+                ("rg1".to_string(), "S1".to_string()),
+                ("rg2".to_string(), "S2".to_string())
+            ]
+        );
+        assert_eq!(
+            sample_from_read_groups(&header, Path::new("test.bam")),
+            Some("S1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_file_stem() {
+        assert_eq!(file_stem(Path::new("data/sample1.cram")), "sample1");
+        assert_eq!(file_stem(Path::new("sample1.BAM")), "sample1");
+        assert_eq!(file_stem(Path::new("cohort.vcf.bgz")), "cohort");
+        assert_eq!(file_stem(Path::new("cohort.vcf.gz")), "cohort");
+        assert_eq!(file_stem(Path::new("symlink")), "symlink");
+    }
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(
+            detect_format(Path::new("test-data/small-test-phased.bam")).unwrap(),
+            InputFormat::Alignment
+        );
+        let unsupported = detect_format(Path::new("test-data/small-test-phased.bam.bai"));
+        assert!(unsupported
+            .unwrap_err()
+            .to_string()
+            .contains("Unsupported file format"));
+    }
+
+    #[test]
+    fn test_split_by_read_group() {
+        // a copy of the chr7 reads of the test bam, in which the reads of the phase sets at an even megabase
+        // (152.7, 154.7 and 156.7 Mb) are in read group rg1 of sample A, and the others in rg2 of sample B
+        let mut reader = bam::Reader::from_path("test-data/small-test-phased.bam").unwrap();
+        let tid = reader.header().tid(b"chr7").unwrap();
+        let mut header = bam::Header::new();
+        header.push_record(
+            bam::header::HeaderRecord::new(b"SQ")
+                .push_tag(b"SN", "chr7")
+                .push_tag(b"LN", reader.header().target_len(tid).unwrap()),
+        );
+        for (id, sample) in [("rg1", "A"), ("rg2", "B")] {
+            header.push_record(
+                bam::header::HeaderRecord::new(b"RG")
+                    .push_tag(b"ID", id)
+                    .push_tag(b"SM", sample),
+            );
+        }
+        let bam = PathBuf::from("test-data/read-groups.bam");
+        {
+            let mut writer = bam::Writer::from_path(&bam, &header, bam::Format::Bam).unwrap();
+            for read in reader.records() {
+                let mut read = read.unwrap();
+                if read.tid() != tid as i32 {
+                    continue;
+                }
+                read.set_tid(0);
+                read.set_mtid(-1);
+                let _ = read.remove_aux(b"RG");
+                let read_group = match get_phaseset(&read).unwrap() {
+                    Some(phaseset) if phaseset / 1000000 % 2 == 1 => "rg2",
+                    _ => "rg1",
+                };
+                read.push_aux(b"RG", Aux::String(read_group)).unwrap();
+                writer.write(&read).unwrap();
+            }
+        }
+        bam::index::build(&bam, None, bam::index::Type::Bai, 1).unwrap();
+        let region = crate::utils::Reg {
+            chrom: "chr7".to_string(),
+            start: 152743763,
+            end: 156779243,
+        };
+        let options = ExtractOptions {
+            threads: 1,
+            split_by_read_group: true,
+            ..Default::default()
+        };
+        let tracks = get_blocks(&bam, &options, std::slice::from_ref(&region)).unwrap();
+        let unsplit = get_blocks(
+            &PathBuf::from("test-data/small-test-phased.bam"),
+            &ExtractOptions {
+                threads: 1,
+                ..Default::default()
+            },
+            &[region],
+        )
+        .unwrap();
+        std::fs::remove_file(&bam).unwrap();
+        std::fs::remove_file("test-data/read-groups.bam.bai").unwrap();
+        // every read has a known read group, so there is no track named after the file
+        let names: Vec<&str> = tracks[0]
+            .iter()
+            .map(|blocks| blocks[0].name.as_str())
+            .collect();
+        assert_eq!(names, ["A", "B"]);
+        for (blocks, parity) in tracks[0].iter().zip([0, 1]) {
+            assert!(!blocks[0].empty);
+            assert!(blocks
+                .iter()
+                .all(|block| block.phaseset / 1000000 % 2 == parity));
+        }
+        let support = |tracks: &[Vec<Blocks>]| -> usize {
+            tracks.iter().flatten().map(|block| block.support).sum()
+        };
+        assert_eq!(support(&tracks[0]), support(&unsplit[0]));
+    }
+
+    #[test]
+    fn test_typed_errors() {
+        let region = crate::utils::Reg {
+            chrom: "chr7".to_string(),
+            start: 152743763,
+            end: 156779243,
+        };
+        let options = ExtractOptions {
+            threads: 1,
+            ..Default::default()
+        };
+        let unindexed = PathBuf::from("test-data/unindexed.bam");
+        std::fs::copy("test-data/small-test-phased.bam", &unindexed).unwrap();
+        assert!(matches!(
+            get_blocks(&unindexed, &options, std::slice::from_ref(&region)),
+            Err(PhasiusError::MissingIndex(_))
+        ));
+        std::fs::remove_file(&unindexed).unwrap();
+        let missing_contig = crate::utils::Reg {
+            chrom: "chrUn".to_string(),
+            ..region
+        };
+        assert!(matches!(
+            get_blocks(
+                &PathBuf::from("test-data/small-test-phased.bam"),
+                &options,
+                &[missing_contig]
+            ),
+            Err(PhasiusError::ContigNotFound { .. })
+        ));
+    }
+
+    /// write a bgzipped and indexed vcf with a single sample S1 on chr7, with PS of the given type
+    fn write_indexed_vcf(vcf: &str, ps_type: &str, records: &str) {
+        use std::io::Write;
+        {
+            let mut writer = rust_htslib::bgzf::Writer::from_path(vcf).unwrap();
+            write!(
+                writer,
+                "##fileformat=VCFv4.2\n##contig=<ID=chr7,length=159345973>\n\
+                 ##FORMAT=<ID=GT,Number=1,Type=String,Description=\"Genotype\">\n\
+                 ##FORMAT=<ID=PS,Number=1,Type={},Description=\"Phase set\">\n\
+                 #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tS1\n{}",
+                ps_type, records
+            )
+            .unwrap();
+        }
+        let c_vcf = CString::new(vcf).unwrap();
+        assert_eq!(
+            unsafe { htslib::tbx_index_build(c_vcf.as_ptr(), 0, &htslib::tbx_conf_vcf) },
+            0
+        );
+    }
+
+    #[test]
+    fn test_explicit_vcf_index() {
+        // a phased vcf, with a space in its name and its index under another name than the one next to it
+        let vcf = "test-data/explicit index.vcf.gz";
+        write_indexed_vcf(
+            vcf,
+            "Integer",
+            "chr7\t1000\t.\tA\tG\t.\tPASS\t.\tGT:PS\t0|1:1000\n\
+             chr7\t2000\t.\tC\tT\t.\tPASS\t.\tGT:PS\t1|0:1000\n",
+        );
+        std::fs::rename(format!("{}.tbi", vcf), "test-data/explicit-index.tbi").unwrap();
+        assert!(matches!(
+            IndexedVcf::from_path(Path::new(vcf)),
+            Err(PhasiusError::MissingIndex(_))
+        ));
+        let options = ExtractOptions {
+            threads: 1,
+            ..Default::default()
+        };
+        let region = crate::utils::Reg {
+            chrom: "chr7".to_string(),
+            start: 0,
+            end: 10000,
+        };
+        let blocks = get_blocks(
+            &PathBuf::from(format!("{}##idx##test-data/explicit-index.tbi", vcf)),
+            &options,
+            &[region],
+        )
+        .unwrap();
+        assert_eq!((blocks[0][0][0].start, blocks[0][0][0].end), (999, 2000));
+        std::fs::remove_file(vcf).unwrap();
+        std::fs::remove_file("test-data/explicit-index.tbi").unwrap();
+    }
+
+    #[test]
+    fn test_vcf_ps_type() {
+        // a PS that is not an integer is reported, as for reads in a bam
+        let vcf = "test-data/float-ps.vcf.gz";
+        write_indexed_vcf(
+            vcf,
+            "Float",
+            "chr7\t1000\t.\tA\tG\t.\tPASS\t.\tGT:PS\t0|1:1000.5\n",
+        );
+        let options = ExtractOptions {
+            threads: 1,
+            ..Default::default()
+        };
+        let region = crate::utils::Reg {
+            chrom: "chr7".to_string(),
+            start: 0,
+            end: 10000,
+        };
+        let result = get_blocks(&PathBuf::from(vcf), &options, &[region]);
+        std::fs::remove_file(vcf).unwrap();
+        std::fs::remove_file(format!("{}.tbi", vcf)).unwrap();
+        assert!(matches!(result, Err(PhasiusError::BadPsType { found, .. }) if found == "Float"));
+    }
+
+    #[test]
+    fn test_detect_format_without_extension() {
+        // the format is detected from the content, not the file name
+        for copy in ["test-data/UPPER-CASE.BAM", "test-data/no-extension"] {
+            std::fs::copy("test-data/small-test-phased.bam", copy).unwrap();
+            assert_eq!(
+                detect_format(Path::new(copy)).unwrap(),
+                InputFormat::Alignment
+            );
+            std::fs::remove_file(copy).unwrap();
+        }
     }
 }
@@ -0,0 +1,140 @@
+use crate::blocks::Blocks;
+use crate::utils::Reg;
+
+/// How well a region is phased across the samples
+#[derive(Debug, PartialEq)]
+pub struct RegionSummary {
+    pub samples: usize,
+    /// samples with at least one phase block
+    pub phased: usize,
+    pub blocks: usize,
+    /// samples with a single block covering the whole region
+    pub spanning: usize,
+}
+
+impl RegionSummary {
+    pub fn new(tracks: &[Vec<Blocks>], region: &Reg) -> RegionSummary {
+        let non_empty: Vec<&Vec<Blocks>> = tracks.iter().filter(|t| !t[0].empty).collect();
+        RegionSummary {
+            samples: tracks.len(),
+            phased: non_empty.len(),
+            blocks: non_empty.iter().map(|t| t.len()).sum(),
+            spanning: non_empty
+                .iter()
+                .filter(|t| {
+                    t.iter().any(|block| {
+                        block.start <= region.start as i64 && block.end >= region.end as i64
+                    })
+                })
+                .count(),
+        }
+    }
+}
+
+/// A region in the index page, with the file name of its phase block map
+pub struct GalleryEntry {
+    pub label: String,
+    pub link: String,
+    pub region: Reg,
+    pub summary: RegionSummary,
+}
+
+/// Create an html page linking to the phase block map of each region, with a table summarizing the phasing
+pub fn index_html(entries: &[GalleryEntry]) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Phase block maps</title>\n\
+         <style>table {border-collapse: collapse;} th, td {border: 1px solid #ccc; padding: 4px 8px; text-align: right;} \
+         td:first-child, td:nth-child(2) {text-align: left;}</style>\n</head>\n<body>\n<h1>Phase block maps</h1>\n<table>\n\
+         <tr><th>name</th><th>region</th><th>samples</th><th>phased samples</th><th>blocks</th>\
+         <th>blocks per phased sample</th><th>samples with a block spanning the region</th></tr>\n",
+    );
+    for entry in entries {
+        let summary = &entry.summary;
+        let per_sample = if summary.phased > 0 {
+            format!("{:.1}", summary.blocks as f64 / summary.phased as f64)
+        } else {
+            "-".to_string()
+        };
+        html.push_str(&format!(
+            "<tr><td><a href=\"{}\">{}</a></td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            escape(&entry.link),
+            escape(&entry.label),
+            entry.region,
+            summary.samples,
+            summary.phased,
+            summary.blocks,
+            per_sample,
+            summary.spanning
+        ));
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    html
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(start: i64, end: i64) -> Blocks {
+        Blocks {
+            start,
+            end,
+            name: "sample".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_region_summary() {
+        let region = Reg {
+            chrom: "chr1".to_string(),
+            start: 1000,
+            end: 2000,
+        };
+        let empty = Blocks {
+            empty: true,
+            ..Default::default()
+        };
+        let tracks = vec![
+            vec![block(500, 2500)],
+            vec![block(900, 1500), block(1600, 2100)],
+            vec![empty],
+        ];
+        assert_eq!(
+            RegionSummary::new(&tracks, &region),
+            RegionSummary {
+                samples: 3,
+                phased: 2,
+                blocks: 3,
+                spanning: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_index_html() {
+        let region = Reg {
+            chrom: "chr1".to_string(),
+            start: 1000,
+            end: 2000,
+        };
+        let summary = RegionSummary::new(&[vec![block(500, 2500)]], &region);
+        let html = index_html(&[GalleryEntry {
+            label: "GENE<1>".to_string(),
+            link: "out.GENE1.html".to_string(),
+            region,
+            summary,
+        }]);
+        assert!(html.contains("<a href=\"out.GENE1.html\">GENE&lt;1&gt;</a>"));
+        assert!(html.contains(
+            "<td>chr1:1000-2000</td><td>1</td><td>1</td><td>1</td><td>1.0</td><td>1</td>"
+        ));
+    }
+}
@@ -5,15 +5,26 @@ use plotly::{Layout, Plot};
 use rayon::prelude::*;
 use std::path::PathBuf;
 
+pub mod aliases;
 pub mod annot;
+pub mod axis;
+pub mod blocklist;
 pub mod blocks;
+pub mod cache;
+pub mod error;
+pub mod export;
 pub mod extract;
+pub mod gallery;
+pub mod preflight;
+pub mod samplesheet;
 pub mod summary;
 pub mod utils;
+pub mod vcf;
 
+/// Check that a file exists, which for file##idx##index is the file before the index
 fn validate_file_exists(path: &str) -> Result<PathBuf, String> {
     let path = PathBuf::from(path);
-    if path.exists() && path.is_file() {
+    if utils::split_explicit_index(&path).0.is_file() {
         Ok(path)
     } else {
         Err(format!(
@@ -23,18 +34,38 @@ fn validate_file_exists(path: &str) -> Result<PathBuf, String> {
     }
 }
 
+fn parse_flags(flags: &str) -> Result<u16, String> {
+    let parsed = match flags.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => flags.parse(),
+    };
+    parsed.map_err(|_| format!("'{}' is not a valid SAM flag", flags))
+}
+
 // The arguments end up in the Cli struct
 #[derive(Parser, Debug)]
 #[command(author, version, about="Tool to draw a map of phaseblocks across crams/bams", long_about = None)]
 struct Cli {
-    /// cram or bam files to check
-    #[arg(required = true, value_parser = validate_file_exists, value_name = "FILE")]
+    /// cram, bam, sam, vcf or bcf files, or phase block lists (whatshap, HapCUT2 or bed) to check
+    #[arg(required_unless_present = "sample_sheet", value_parser = validate_file_exists, value_name = "FILE")]
     input: Vec<PathBuf>,
 
+    /// tab-separated sample sheet with columns sample_id, path, optionally group and other metadata, instead of files
+    #[arg(long, value_parser = validate_file_exists, conflicts_with = "input")]
+    sample_sheet: Option<PathBuf>,
+
     /// bed file annotation to use (bgzipped and tabix indexed)
     #[arg(short, long, value_parser = validate_file_exists)]
     bed: Option<PathBuf>,
 
+    /// collapse the space between the bed features (e.g. introns) on the x-axis
+    #[arg(long, requires = "bed")]
+    compress_axis: bool,
+
+    /// width of the collapsed space between bed features with --compress-axis
+    #[arg(long, default_value_t = 200, value_parser = clap::value_parser!(i64).range(0..))]
+    gap_width: i64,
+
     /// Number of crams/bams to parse in parallel
     #[arg(short, long, default_value_t = 4)]
     threads: usize,
@@ -47,9 +78,26 @@ struct Cli {
     #[arg(short, long)]
     output: String,
 
-    /// region string to plot phase blocks from
+    /// region to plot phase blocks from, as chrom:start-end, chrom:pos, chrom:pos+flank, a whole contig
+    /// or a gene name in the --bed or --gtf file [default: every contig, with an output per contig]
     #[arg(short, long)]
-    region: String,
+    region: Option<String>,
+
+    /// coordinates of --region are 1-based and closed, rather than 0-based and half-open
+    #[arg(long)]
+    one_based: bool,
+
+    /// extend the region(s) with this many bp on both sides
+    #[arg(long, default_value_t = 0)]
+    flank: u32,
+
+    /// gtf file (optionally bgzipped) to look up gene names given as --region
+    #[arg(long, value_parser = validate_file_exists)]
+    gtf: Option<PathBuf>,
+
+    /// bed file of regions to plot, with a map per region and an index page with links at --output
+    #[arg(long, value_parser = validate_file_exists, conflicts_with = "region")]
+    regions: Option<PathBuf>,
 
     /// line width
     #[arg(short, long)]
@@ -59,9 +107,98 @@ struct Cli {
     #[arg(long)]
     summary: Option<String>,
 
+    /// table with a line per block (long format)
+    #[arg(long)]
+    block_table: Option<String>,
+
+    /// table with phasing metrics per sample (N50, NG50, phased bp, ...), computed on the blocks within the region
+    #[arg(long)]
+    metrics: Option<String>,
+
     /// strictly plot the begin and end of the specified interval, not the whole interval gathered from blocks
     #[arg(long)]
     strict: bool,
+
+    /// comma-separated list of samples to plot from multi-sample VCF files [default: all samples]
+    #[arg(long, value_delimiter = ',')]
+    samples: Option<Vec<String>>,
+
+    /// infer phase blocks from phased genotypes (0|1) in VCF records without PS tag, unphased heterozygous genotypes break blocks
+    #[arg(long)]
+    phase_from_gt: bool,
+
+    /// only use VCF records with FILTER PASS (or no filter)
+    #[arg(long)]
+    pass_only: bool,
+
+    /// only use heterozygous genotypes from VCF files
+    #[arg(long)]
+    het_only: bool,
+
+    /// minimum mapping quality of reads from crams/bams
+    #[arg(long, default_value_t = 0)]
+    min_mapq: u8,
+
+    /// only use reads with all of these flags set (decimal or hexadecimal)
+    #[arg(long, default_value = "0", value_parser = parse_flags)]
+    include_flags: u16,
+
+    /// ignore reads with any of these flags set (decimal or hexadecimal) [default: unmapped and secondary]
+    #[arg(long, default_value = "0x104", value_parser = parse_flags)]
+    exclude_flags: u16,
+
+    /// minimum aligned length on the reference of reads from crams/bams
+    #[arg(long, default_value_t = 0)]
+    min_aligned_length: i64,
+
+    /// reference fasta used to decode cram files
+    #[arg(long, value_parser = validate_file_exists)]
+    reference: Option<PathBuf>,
+
+    /// fasta index (.fai) to check the contig lengths of the inputs against [default: the index of --reference, if present]
+    #[arg(long, value_parser = validate_file_exists)]
+    fai: Option<PathBuf>,
+
+    /// exit with an error rather than a warning when inputs disagree about the length of a contig
+    #[arg(long)]
+    fail_on_build_mismatch: bool,
+
+    /// name tracks after the sample in the file header (SM tag of bam read groups, VCF sample) instead of the file name
+    #[arg(long)]
+    name_from_header: bool,
+
+    /// create a track per sample (SM tag) in the read groups of each cram/bam
+    #[arg(long)]
+    split_by_read_group: bool,
+
+    /// split phase sets in multiple blocks where no read or variant supports them for more than this many bp
+    #[arg(long, value_parser = clap::value_parser!(i64).range(0..))]
+    max_gap: Option<i64>,
+
+    /// table of alternative contig names, with the names of a contig on a line separated by tabs or commas.
+    /// Adding or removing the chr prefix and matching chrM/MT is always tried
+    #[arg(long, value_parser = validate_file_exists)]
+    contig_aliases: Option<PathBuf>,
+
+    /// write the blocks to a bgzipped and tabix-indexed bed file, with a row per block (chrom, start, end, sample, phase set and support)
+    #[arg(long)]
+    blocks_bed: Option<PathBuf>,
+
+    /// continue when extraction fails for some inputs, drawing them as failed rows and exiting with an error at the end
+    #[arg(long)]
+    keep_going: bool,
+
+    /// directory to cache extracted blocks in, so that re-runs with other plotting or summary options skip reading the inputs
+    #[arg(long)]
+    cache_dir: Option<PathBuf>,
+
+    /// remove the blocks cached in --cache-dir before extraction
+    #[arg(long, requires = "cache_dir")]
+    clear_cache: bool,
+
+    /// neither read from nor write to --cache-dir
+    #[arg(long)]
+    no_cache: bool,
 }
 
 fn main() {
@@ -70,58 +207,535 @@ fn main() {
     let args = Cli::parse();
     log::debug!("Parsed command line arguments: {:?}", args);
     info!("Collected arguments");
-    run_phasius(args);
+    let failed = run_phasius(args);
+    if failed > 0 {
+        eprintln!("Error: Extraction failed for {} samples", failed);
+        std::process::exit(1);
+    }
     log::debug!("phasius completed successfully");
 }
 
-fn run_phasius(args: Cli) {
-    log::debug!("Starting run_phasius with region: {}", args.region);
-    let target = utils::process_region(&args.region).expect("Error: Improper interval!");
-    log::debug!("Parsed region: {:?}", target);
-    log::debug!(
-        "Extracting blocks from {} files with {} threads",
-        args.input.len(),
-        args.threads
+/// Create the phase block maps, returning the number of samples for which extraction failed
+fn run_phasius(args: Cli) -> usize {
+    log::debug!("Starting run_phasius with region: {:?}", args.region);
+    let input = inputs(&args);
+    let aliases = contig_aliases(&args);
+    let cache = block_cache(&args);
+    // the contigs of the inputs, once read for genome-wide mode
+    let mut input_contigs_read = None;
+    // the label of each target names its output when there is an output per target
+    let (targets, labels): (Vec<utils::Reg>, Vec<String>) = match (&args.region, &args.regions) {
+        (_, Some(regions)) => {
+            let regions = utils::parse_regions_bed(regions)
+                .expect("Failure when parsing regions from bed file");
+            let labels = unique_labels(
+                regions
+                    .iter()
+                    .map(|(reg, name)| name.clone().unwrap_or_else(|| reg.to_string()))
+                    .collect(),
+            );
+            (
+                regions
+                    .into_iter()
+                    .map(|(reg, _)| reg.with_flank(args.flank))
+                    .collect(),
+                labels,
+            )
+        }
+        (Some(region), None) => {
+            let target = resolve_region(region, &args).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(1)
+            });
+            let label = target.to_string();
+            (vec![target], vec![label])
+        }
+        (None, None) => {
+            let contigs = input_contigs_read.insert(input_contigs(
+                &input,
+                &aliases,
+                args.keep_going,
+                cache.as_ref(),
+            ));
+            log::debug!("No region specified, using all {} contigs", contigs.len());
+            (
+                contigs
+                    .iter()
+                    .map(|(chrom, _)| utils::Reg::contig(chrom))
+                    .collect(),
+                unique_labels(contigs.iter().map(|(chrom, _)| chrom.clone()).collect()),
+            )
+        }
+    };
+    let targets = resolve_contig_lengths(
+        targets,
+        input_contigs_read,
+        &input,
+        &aliases,
+        args.keep_going,
+        cache.as_ref(),
+        args.flank > 0 || args.region.as_deref().is_some_and(utils::has_flank),
+    )
+    .unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        std::process::exit(1)
+    });
+    log::debug!("Parsed regions: {:?}", targets);
+    let genome_wide = args.region.is_none() && args.regions.is_none();
+    // in genome-wide mode the contigs come from the inputs, and not every input needs to have all of them
+    let paths: Vec<PathBuf> = input.iter().map(|sample| sample.path.clone()).collect();
+    let options = extract_options(&args);
+    let (headers, problems) = preflight::check_inputs(
+        &paths,
+        &targets,
+        !genome_wide,
+        &aliases,
+        args.threads,
+        cache.as_ref().map(|cache| (cache, &options)),
     );
-    let blocks_per_bam = extract_blocks(&args, &target);
+    if !problems.is_empty() {
+        let level = if args.keep_going { "Warning" } else { "Error" };
+        for problem in &problems {
+            eprintln!("{}: {}", level, problem);
+        }
+        if !args.keep_going {
+            eprintln!(
+                "Error: Found {} problems with the input files, not starting extraction",
+                problems.len()
+            );
+            std::process::exit(1);
+        }
+    }
+    let fai = args.fai.clone().or_else(|| {
+        args.reference
+            .as_ref()
+            .map(|reference| PathBuf::from(format!("{}.fai", reference.display())))
+            .filter(|fai| fai.exists())
+    });
+    let mismatches = preflight::check_lengths(&headers, &targets, &aliases, fai.as_deref());
+    if !mismatches.is_empty() {
+        let level = if args.fail_on_build_mismatch {
+            "Error"
+        } else {
+            "Warning"
+        };
+        for mismatch in &mismatches {
+            eprintln!("{}: {}", level, mismatch);
+        }
+        if args.fail_on_build_mismatch {
+            std::process::exit(1);
+        }
+    }
+    let blocks_per_target = extract_blocks(&args, &targets, cache.as_ref());
     log::debug!("Extracted blocks from all files");
-    log::debug!("Starting plot generation");
-    plot_blocks(&blocks_per_bam, &args, target);
-    log::debug!("Plot generation complete");
-    if let Some(summary) = args.summary {
-        log::debug!("Generating summary file: {}", summary);
-        let summary_per_sample = summary::summarize(&blocks_per_bam);
-        // write the summary_per_sample to a file
-        std::fs::write(&summary, summary_per_sample).expect("Unable to write file");
-        log::debug!("Summary file written: {}", summary);
+    let mut failed: Vec<&str> = vec![];
+    for tracks in &blocks_per_target {
+        for track in tracks.iter().filter(|track| track[0].error.is_some()) {
+            if !failed.contains(&track[0].name.as_str()) {
+                failed.push(&track[0].name);
+            }
+        }
+    }
+    let failed = failed.len();
+    if let Some(path) = &args.blocks_bed {
+        log::debug!("Writing blocks to: {}", path.display());
+        export::write_bed(&targets, &blocks_per_target, path).unwrap_or_else(|e| {
+            eprintln!("Error: Unable to write blocks to {}: {}", path.display(), e);
+            std::process::exit(1)
+        });
+    }
+    let output_per_target = args.region.is_none();
+    let mut gallery = vec![];
+    for ((target, label), blocks_per_bam) in targets.into_iter().zip(labels).zip(blocks_per_target)
+    {
+        // without a region, contigs without any phase block are not worth an output
+        if genome_wide && blocks_per_bam.iter().all(|blocks| blocks[0].empty) {
+            log::debug!("No phase blocks on {}, skipping", target.chrom);
+            continue;
+        }
+        let output = if output_per_target {
+            labelled_output_name(&args.output, &label)
+        } else {
+            args.output.clone()
+        };
+        log::debug!("Starting plot generation");
+        plot_blocks(&blocks_per_bam, &args, &target, &output, &aliases);
+        log::debug!("Plot generation complete");
+        if let Some(summary) = &args.summary {
+            let summary = if output_per_target {
+                labelled_output_name(summary, &label)
+            } else {
+                summary.clone()
+            };
+            log::debug!("Generating summary file: {}", summary);
+            let summary_per_sample = summary::summarize(&blocks_per_bam);
+            // write the summary_per_sample to a file
+            std::fs::write(&summary, summary_per_sample).expect("Unable to write file");
+            log::debug!("Summary file written: {}", summary);
+        }
+        let write_table = |file: &String, table: String| {
+            let file = if output_per_target {
+                labelled_output_name(file, &label)
+            } else {
+                file.clone()
+            };
+            log::debug!("Writing table: {}", file);
+            std::fs::write(&file, table).expect("Unable to write file");
+        };
+        if let Some(file) = &args.block_table {
+            write_table(file, summary::block_table(&blocks_per_bam, &target));
+        }
+        if let Some(file) = &args.metrics {
+            write_table(file, summary::metrics(&blocks_per_bam, &target));
+        }
+        if args.regions.is_some() {
+            gallery.push(gallery::GalleryEntry {
+                summary: gallery::RegionSummary::new(&blocks_per_bam, &target),
+                link: std::path::Path::new(&output)
+                    .file_name()
+                    .map_or(output.clone(), |f| f.to_string_lossy().to_string()),
+                label,
+                region: target,
+            });
+        }
+    }
+    if args.regions.is_some() {
+        log::debug!("Writing index page to: {}", args.output);
+        std::fs::write(&args.output, gallery::index_html(&gallery)).expect("Unable to write file");
     }
     log::debug!("run_phasius completed");
+    failed
+}
+
+/// Make labels safe to use in file names, and unique by appending a number to repeated labels
+fn unique_labels(labels: Vec<String>) -> Vec<String> {
+    let mut unique: Vec<String> = vec![];
+    for label in labels {
+        let label: String = label
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || "._-".contains(c) {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let mut candidate = label.clone();
+        let mut count = 1;
+        while unique.contains(&candidate) {
+            count += 1;
+            candidate = format!("{}_{}", label, count);
+        }
+        unique.push(candidate);
+    }
+    unique
+}
+
+fn inputs(args: &Cli) -> Vec<samplesheet::Sample> {
+    match &args.sample_sheet {
+        Some(sample_sheet) => samplesheet::parse_sample_sheet(sample_sheet)
+            .expect("Failure when parsing sample sheet"),
+        None => args
+            .input
+            .iter()
+            .cloned()
+            .map(samplesheet::Sample::from_path)
+            .collect(),
+    }
+}
+
+/// The contigs of all inputs, in the order they are first seen, with the largest length found for each.
+/// Contigs named differently in different inputs (such as chr7 and 7) are listed once, by their first name seen.
+/// With keep_going, inputs of which the contigs cannot be read are skipped, to fail at extraction.
+fn input_contigs(
+    input: &[samplesheet::Sample],
+    aliases: &aliases::ContigAliases,
+    keep_going: bool,
+    cache: Option<&cache::BlockCache>,
+) -> extract::Contigs {
+    let mut contigs: extract::Contigs = vec![];
+    for sample in input {
+        let cached = cache.and_then(|cache| cache.get_contigs(&sample.path));
+        let file_contigs = match cached.map_or_else(|| extract::get_contigs(&sample.path), Ok) {
+            Ok(file_contigs) => file_contigs,
+            Err(e) if keep_going => {
+                eprintln!("Warning: {}", e);
+                continue;
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1)
+            }
+        };
+        for (chrom, length) in file_contigs {
+            match contigs
+                .iter_mut()
+                .find(|(c, _)| aliases.equivalent(c, &chrom))
+            {
+                Some((_, l)) => *l = (*l).max(length),
+                None => contigs.push((chrom, length)),
+            }
+        }
+    }
+    contigs
+}
+
+/// Set the end of regions spanning a whole contig to the contig length.
+/// With clamp, for regions extended with --flank or given as chrom:pos+flank, the end of the other regions is limited to the contig length.
+/// The contigs of the inputs are only read if they were not already.
+fn resolve_contig_lengths(
+    targets: Vec<utils::Reg>,
+    contigs: Option<extract::Contigs>,
+    input: &[samplesheet::Sample],
+    aliases: &aliases::ContigAliases,
+    keep_going: bool,
+    cache: Option<&cache::BlockCache>,
+    clamp: bool,
+) -> Result<Vec<utils::Reg>, String> {
+    if !clamp && !targets.iter().any(|target| target.is_whole_contig()) {
+        return Ok(targets);
+    }
+    let contigs = contigs.unwrap_or_else(|| input_contigs(input, aliases, keep_going, cache));
+    targets
+        .into_iter()
+        .map(|target| {
+            let contig = contigs
+                .iter()
+                .find(|(chrom, _)| aliases.equivalent(chrom, &target.chrom));
+            if !target.is_whole_contig() {
+                // contigs missing from the inputs are reported by the preflight checks
+                return Ok(match contig {
+                    Some((_, Some(length))) if target.end as u64 > *length => {
+                        log::debug!("Clamping {} to the contig length {}", target, length);
+                        utils::Reg {
+                            end: *length as u32,
+                            ..target
+                        }
+                    }
+                    _ => target,
+                });
+            }
+            match contig {
+                Some((_, Some(length))) => Ok(utils::Reg {
+                    end: (*length).min(u32::MAX as u64) as u32,
+                    ..target
+                }),
+                Some((_, None)) => {
+                    eprintln!(
+                        "Warning: Length of contig {} not found in the input files",
+                        target.chrom
+                    );
+                    Ok(target)
+                }
+                None => Err(format!(
+                    "{} is not a contig in the input files, nor a gene in the --bed or --gtf file",
+                    target.chrom
+                )),
+            }
+        })
+        .collect()
+}
+
+/// Get the region to plot from a region string or, if it has no coordinates, a gene name in the bed or gtf file
+fn resolve_region(region: &str, args: &Cli) -> Result<utils::Reg, Box<dyn std::error::Error>> {
+    if !region.contains(':') {
+        if let Some(gene) = annot::find_gene(region, args.bed.as_deref(), args.gtf.as_deref())? {
+            log::debug!("Found gene {} at {}", region, gene);
+            return Ok(gene.with_flank(args.flank));
+        }
+    }
+    Ok(utils::process_region(region, args.one_based)?.with_flank(args.flank))
 }
 
-fn extract_blocks(args: &Cli, target: &utils::Reg) -> Vec<Vec<blocks::Blocks>> {
+/// Add a label (contig or region name) to an output file name, before its extension (out.html becomes out.chr1.html)
+fn labelled_output_name(output: &str, label: &str) -> String {
+    let path = std::path::Path::new(output);
+    match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(extension)) => path
+            .with_file_name(format!(
+                "{}.{}.{}",
+                stem.to_string_lossy(),
+                label,
+                extension.to_string_lossy()
+            ))
+            .to_string_lossy()
+            .to_string(),
+        _ => format!("{}.{}", output, label),
+    }
+}
+
+/// Extract the blocks of all inputs for each of the targets, returning the tracks per target
+fn extract_blocks(
+    args: &Cli,
+    targets: &[utils::Reg],
+    cache: Option<&cache::BlockCache>,
+) -> Vec<extract::Tracks> {
+    let input = inputs(args);
     log::debug!(
-        "Extracting blocks from {} files with {} threads",
-        args.input.len(),
+        "Extracting blocks from {} files in {} regions with {} threads",
+        input.len(),
+        targets.len(),
         args.threads
     );
-    let input = args.input.clone();
+    let options = extract_options(args);
     let pool = rayon::ThreadPoolBuilder::new()
         .num_threads(args.threads)
         .build()
         .unwrap();
 
-    pool.install(|| {
-        input
-            .into_par_iter()
-            .map(|b| {
-                extract::get_blocks(&b, args.decompression, target)
-                    .expect("Failure when parsing region from bam file.")
-            })
-            .collect()
-    })
+    // a batch of regions is fetched from each input after opening it once,
+    // whereas the contigs of the genome-wide mode are extracted in parallel
+    let chunk_size = if args.regions.is_some() {
+        targets.len().max(1)
+    } else {
+        1
+    };
+    let jobs: Vec<(&samplesheet::Sample, &[utils::Reg])> = input
+        .iter()
+        .flat_map(|sample| targets.chunks(chunk_size).map(move |chunk| (sample, chunk)))
+        .collect();
+    // without keep_going, the first failure stops the extraction
+    let blocks_per_job: Result<Vec<Vec<extract::Tracks>>, error::PhasiusError> =
+        pool.install(|| {
+            jobs.into_par_iter()
+                .map(|(sample, chunk)| {
+                    let extracted = match cache {
+                        Some(cache) => cache.get_blocks(&sample.path, &options, chunk),
+                        None => extract::get_blocks(&sample.path, &options, chunk),
+                    };
+                    let tracks = match extracted {
+                        Ok(tracks) => tracks,
+                        Err(e) if args.keep_going => {
+                            eprintln!("Error: {}", e);
+                            failed_tracks(&sample.path, chunk, &e)
+                        }
+                        Err(e) => return Err(e),
+                    };
+                    Ok(tracks
+                        .into_iter()
+                        .map(|tracks| sample.label(tracks))
+                        .collect())
+                })
+                .collect()
+        });
+    let blocks_per_job = blocks_per_job.unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        std::process::exit(1)
+    });
+    let mut blocks_per_target: Vec<extract::Tracks> = vec![vec![]; targets.len()];
+    // the jobs of an input cover all targets in order
+    for (index, tracks) in blocks_per_job.into_iter().flatten().enumerate() {
+        // a single (multi-sample) file can result in multiple tracks
+        blocks_per_target[index % targets.len()].extend(tracks);
+    }
+    // sample ids in a sample sheet can deliberately be repeated for multiple files of a sample
+    if args.sample_sheet.is_none() {
+        if let Some(tracks) = blocks_per_target.first() {
+            warn_duplicate_names(tracks);
+        }
+    }
+    blocks_per_target
 }
 
-fn plot_blocks(blocks_per_bam: &[Vec<blocks::Blocks>], args: &Cli, target: utils::Reg) {
+/// The block cache in --cache-dir, if used. Without a usable cache directory, blocks are extracted as usual.
+fn block_cache(args: &Cli) -> Option<cache::BlockCache> {
+    let dir = args.cache_dir.as_ref()?;
+    let cache = cache::BlockCache::new(dir)
+        .map_err(|e| {
+            eprintln!(
+                "Warning: Unable to use cache directory {}: {}",
+                dir.display(),
+                e
+            )
+        })
+        .ok()?;
+    if args.clear_cache {
+        match cache.clear() {
+            Ok(removed) => log::debug!("Removed {} cached entries", removed),
+            Err(e) => eprintln!(
+                "Warning: Unable to clear cache directory {}: {}",
+                dir.display(),
+                e
+            ),
+        }
+    }
+    if args.no_cache {
+        None
+    } else {
+        Some(cache)
+    }
+}
+
+/// A failed row spanning each region, for an input that could not be extracted
+fn failed_tracks(
+    path: &std::path::Path,
+    regions: &[utils::Reg],
+    error: &error::PhasiusError,
+) -> Vec<extract::Tracks> {
+    regions
+        .iter()
+        .map(|region| {
+            vec![vec![blocks::Blocks {
+                start: region.start as i64,
+                end: region.end as i64,
+                name: extract::file_stem(path),
+                empty: true,
+                error: Some(error.to_string()),
+                ..Default::default()
+            }]]
+        })
+        .collect()
+}
+
+fn warn_duplicate_names(tracks: &[Vec<blocks::Blocks>]) {
+    let mut counts: Vec<(&str, usize)> = vec![];
+    for track in tracks {
+        match counts.iter_mut().find(|(name, _)| *name == track[0].name) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((&track[0].name, 1)),
+        }
+    }
+    for (name, count) in counts.into_iter().filter(|(_, count)| *count > 1) {
+        eprintln!("Warning: {} inputs are named {}", count, name);
+    }
+}
+
+fn extract_options(args: &Cli) -> extract::ExtractOptions {
+    extract::ExtractOptions {
+        threads: args.decompression,
+        samples: args.samples.clone(),
+        phase_from_gt: args.phase_from_gt,
+        pass_only: args.pass_only,
+        het_only: args.het_only,
+        min_mapq: args.min_mapq,
+        include_flags: args.include_flags,
+        exclude_flags: args.exclude_flags,
+        min_aligned_length: args.min_aligned_length,
+        reference: args.reference.clone(),
+        name_from_header: args.name_from_header,
+        split_by_read_group: args.split_by_read_group,
+        max_gap: args.max_gap,
+        aliases: contig_aliases(args),
+        allow_missing_contigs: args.region.is_none() && args.regions.is_none(),
+    }
+}
+
+fn contig_aliases(args: &Cli) -> aliases::ContigAliases {
+    match &args.contig_aliases {
+        Some(path) => aliases::ContigAliases::from_file(path)
+            .expect("Failure when parsing contig alias table"),
+        None => aliases::ContigAliases::default(),
+    }
+}
+
+fn plot_blocks(
+    blocks_per_bam: &[Vec<blocks::Blocks>],
+    args: &Cli,
+    target: &utils::Reg,
+    output: &str,
+    aliases: &aliases::ContigAliases,
+) {
     log::debug!("Plotting {} samples", blocks_per_bam.len());
     let mut plot = Plot::new();
     let default_colors = [
@@ -141,32 +755,82 @@ fn plot_blocks(blocks_per_bam: &[Vec<blocks::Blocks>], args: &Cli, target: utils
     } else {
         None
     };
-    let non_empty_blocks: Vec<_> = blocks_per_bam
+    let annotation = args.bed.clone().map(|p| {
+        log::debug!("Processing bed annotation file: {:?}", p);
+        annot::parse_bed(p, target, aliases).expect("Failure when parsing annotation from bed file")
+    });
+    let axis = match &annotation {
+        Some(annotation) if args.compress_axis => {
+            let features: Vec<(i64, i64)> = annotation.iter().map(|a| (a.begin, a.end)).collect();
+            if features.is_empty() {
+                eprintln!(
+                    "Warning: No bed features in {}, not compressing the axis",
+                    target
+                );
+                None
+            } else {
+                Some(axis::CompressedAxis::new(
+                    &features,
+                    target.start as i64,
+                    target.end as i64,
+                    args.gap_width,
+                ))
+            }
+        }
+        _ => None,
+    };
+    let mut non_empty_blocks: Vec<_> = blocks_per_bam
         .iter()
-        .filter(|blocks| !blocks[0].empty)
+        .filter(|blocks| !blocks[0].empty || blocks[0].error.is_some())
         .collect();
+    // keep samples of the same group together
+    non_empty_blocks.sort_by_key(|blocks| blocks[0].group.clone());
     for (height, blocks) in non_empty_blocks.iter().enumerate() {
         let mut show_legend = true;
+        // overlapping blocks are alternately shifted up and down, so they don't hide each other
+        let mut shift = 0.2;
         for (block, color) in blocks.iter().zip(default_colors.iter().cycle()) {
-            plot.add_trace(block.plot(height, color.to_string(), show_legend, args.width, limits));
+            let y = if block.overlapping {
+                shift = -shift;
+                height as f64 + shift
+            } else {
+                height as f64
+            };
+            plot.add_trace(block.plot(
+                y,
+                color.to_string(),
+                show_legend,
+                args.width,
+                limits,
+                axis.as_ref(),
+            ));
             show_legend = false;
         }
     }
-    if let Some(p) = args.bed.clone() {
-        log::debug!("Processing bed annotation file: {:?}", p);
-        for annot_interval in annot::parse_bed(p, &target)
-            .expect("Failure when parsing annotation from bed file")
-            .into_iter()
-        {
+    if let Some(annotation) = annotation {
+        for mut annot_interval in annotation {
             log::debug!("Adding annotation trace");
+            if let Some(axis) = &axis {
+                annot_interval.begin = axis.map(annot_interval.begin);
+                annot_interval.end = axis.map(annot_interval.end);
+            }
             plot.add_trace(annot_interval.plot())
         }
         log::debug!("Bed annotations added");
     }
+    let mut x_axis = Axis::new();
+    if let Some(axis) = &axis {
+        for marker in axis.break_markers(-2.5, non_empty_blocks.len() as f64) {
+            plot.add_trace(marker);
+        }
+        let (tick_values, tick_text) = axis.ticks();
+        x_axis = x_axis.tick_values(tick_values).tick_text(tick_text);
+    }
     log::debug!("Setting plot layout");
     plot.set_layout(
         Layout::new()
-            .title(format!("Phase block map {}", args.region))
+            .title(format!("Phase block map {}", target))
+            .x_axis(x_axis)
             .y_axis(
                 Axis::new()
                     .show_line(false)
@@ -178,8 +842,8 @@ fn plot_blocks(blocks_per_bam: &[Vec<blocks::Blocks>], args: &Cli, target: utils
             .height(1000)
             .legend(Legend::new().trace_group_gap(0)),
     );
-    log::debug!("Writing HTML output to: {}", args.output);
-    plot.write_html(args.output.clone());
+    log::debug!("Writing HTML output to: {}", output);
+    plot.write_html(output);
     log::debug!("HTML output written successfully");
 }
 
@@ -196,148 +860,535 @@ fn verify_app() {
 }
 
 #[test]
-fn run() {
-    let test_cli = Cli {
+fn test_parse_flags() {
+    assert_eq!(parse_flags("260"), Ok(260));
+    assert_eq!(parse_flags("0x904"), Ok(2308));
+    assert!(parse_flags("secondary").is_err());
+}
+
+#[test]
+fn test_negative_gaps() {
+    let parse = |option: &str| {
+        Cli::try_parse_from([
+            "phasius",
+            "--output",
+            "test.html",
+            "test-data/small-test-phased.bam",
+            option,
+        ])
+    };
+    assert_eq!(parse("--max-gap=1000").unwrap().max_gap, Some(1000));
+    for option in ["--max-gap=-1", "--gap-width=-200"] {
+        assert_eq!(
+            parse(option).unwrap_err().kind(),
+            clap::error::ErrorKind::ValueValidation
+        );
+    }
+}
+
+/// The arguments of a run on the test bam, to override per test
+#[cfg(test)]
+fn test_cli() -> Cli {
+    Cli {
         input: vec![
             PathBuf::from("test-data/small-test-phased.bam"),
             PathBuf::from("test-data/small-test-phased.bam"),
             PathBuf::from("test-data/small-test-phased.bam"),
         ],
+        sample_sheet: None,
         bed: None,
+        gtf: None,
+        compress_axis: false,
+        gap_width: 200,
         threads: 2,
         decompression: 1,
         output: "test.html".to_string(),
-        region: "chr7:152743763-156779243".to_string(),
+        region: Some("chr7:152743763-156779243".to_string()),
+        regions: None,
+        one_based: false,
+        flank: 0,
         width: None,
         summary: None,
+        block_table: None,
+        metrics: None,
         strict: false,
+        samples: None,
+        phase_from_gt: false,
+        pass_only: false,
+        het_only: false,
+        min_mapq: 0,
+        include_flags: 0,
+        exclude_flags: 0x104,
+        min_aligned_length: 0,
+        reference: None,
+        fai: None,
+        fail_on_build_mismatch: false,
+        name_from_header: false,
+        split_by_read_group: false,
+        max_gap: None,
+        contig_aliases: None,
+        blocks_bed: None,
+        keep_going: false,
+        cache_dir: None,
+        clear_cache: false,
+        no_cache: false,
+    }
+}
+
+#[test]
+fn run() {
+    run_phasius(test_cli());
+}
+
+#[test]
+fn run_with_cache() {
+    std::fs::copy("test-data/small-test-phased.bam", "test-data/cached.bam").unwrap();
+    std::fs::copy(
+        "test-data/small-test-phased.bam.bai",
+        "test-data/cached.bam.bai",
+    )
+    .unwrap();
+    let cli = |clear_cache| Cli {
+        input: vec![PathBuf::from("test-data/cached.bam")],
+        cache_dir: Some(PathBuf::from("test-data/run-cache")),
+        clear_cache,
+        ..test_cli()
     };
-    run_phasius(test_cli);
+    assert_eq!(run_phasius(cli(true)), 0);
+    // an entry for the blocks and one for the contigs
+    assert_eq!(std::fs::read_dir("test-data/run-cache").unwrap().count(), 2);
+    // the second run reads the contigs and blocks from the cache, and does not need the index to check
+    // or extract the input
+    std::fs::remove_file("test-data/cached.bam.bai").unwrap();
+    assert_eq!(run_phasius(cli(false)), 0);
+    std::fs::remove_file("test-data/cached.bam").unwrap();
+    std::fs::remove_dir_all("test-data/run-cache").unwrap();
+}
+
+#[test]
+fn run_with_contig_alias() {
+    let args = Cli {
+        region: Some("7:152743763-156779243".to_string()),
+        ..test_cli()
+    };
+    // the bam names the contig chr7
+    assert_eq!(run_phasius(args), 0);
 }
 
 #[test]
 fn run_with_width() {
-    let test_cli = Cli {
-        input: vec![
-            PathBuf::from("test-data/small-test-phased.bam"),
-            PathBuf::from("test-data/small-test-phased.bam"),
-            PathBuf::from("test-data/small-test-phased.bam"),
-        ],
-        bed: None,
-        threads: 2,
-        decompression: 1,
-        output: "test.html".to_string(),
-        region: "chr7:152743763-156779243".to_string(),
+    let args = Cli {
         width: Some(4),
-        summary: None,
-        strict: false,
+        ..test_cli()
     };
-    run_phasius(test_cli);
+    run_phasius(args);
 }
 
 #[test]
 fn run_with_commas() {
-    let test_cli = Cli {
+    let args = Cli {
         input: vec![
             PathBuf::from("test-data/small-test-phased.bam"),
             PathBuf::from("test-data/small-test-phased.bam"),
             PathBuf::from("test-data/small-test-phased.bam"),
             PathBuf::from("test-data/small-test-phased.bam"),
         ],
-        bed: None,
-        threads: 2,
-        decompression: 1,
-        output: "test.html".to_string(),
-        region: "chr7:152,743,763-156,779,243".to_string(),
-        width: None,
-        summary: None,
-        strict: false,
+        region: Some("chr7:152,743,763-156,779,243".to_string()),
+        ..test_cli()
+    };
+    run_phasius(args);
+}
+
+#[test]
+fn run_with_flank_at_contig_end() {
+    // chr7 is 159345973 bp long, the flank is clamped to the end of the contig
+    let args = Cli {
+        region: Some("chr7:159000000-159300000".to_string()),
+        flank: 500000,
+        ..test_cli()
     };
-    run_phasius(test_cli);
+    assert_eq!(run_phasius(args), 0);
 }
 
 #[test]
-fn run_with_summary() {
-    let test_cli = Cli {
+fn run_with_position_flank_at_contig_end() {
+    // the flank of chrom:pos+flank is clamped to the end of the contig as well
+    let args = Cli {
+        region: Some("chr7:159345000+5000".to_string()),
+        ..test_cli()
+    };
+    assert_eq!(run_phasius(args), 0);
+}
+
+#[test]
+fn run_with_position_and_flank() {
+    let args = Cli {
         input: vec![
             PathBuf::from("test-data/small-test-phased.bam"),
             PathBuf::from("test-data/small-test-phased.bam"),
             PathBuf::from("test-data/small-test-phased.bam"),
+            PathBuf::from("test-data/small-test-phased.bam"),
         ],
-        bed: None,
-        threads: 2,
-        decompression: 1,
-        output: "test.html".to_string(),
-        region: "chr7:152743763-156779243".to_string(),
-        width: None,
+        region: Some("chr7:154,000,000".to_string()),
+        one_based: true,
+        flank: 500000,
+        ..test_cli()
+    };
+    run_phasius(args);
+}
+
+#[test]
+fn run_with_summary() {
+    let args = Cli {
         summary: Some("test_summary.txt".to_string()),
-        strict: false,
+        ..test_cli()
     };
-    run_phasius(test_cli);
+    run_phasius(args);
+}
+
+#[test]
+fn run_with_tables() {
+    let args = Cli {
+        block_table: Some("test_block_table.tsv".to_string()),
+        metrics: Some("test_metrics.tsv".to_string()),
+        ..test_cli()
+    };
+    run_phasius(args);
+    let table = std::fs::read_to_string("test_block_table.tsv").unwrap();
+    assert!(table.lines().nth(1).unwrap().contains("\tchr7\t"));
+    let metrics = std::fs::read_to_string("test_metrics.tsv").unwrap();
+    assert_eq!(metrics.lines().count(), 4);
+}
+
+#[test]
+fn run_with_samples() {
+    use std::io::Write;
+    // a phased vcf with two samples, of which the requested ones are plotted
+    let vcf = "test-data/two-samples.vcf.gz";
+    {
+        let mut writer = rust_htslib::bgzf::Writer::from_path(vcf).unwrap();
+        write!(
+            writer,
+            "##fileformat=VCFv4.2\n##contig=<ID=chr7,length=159345973>\n\
+             ##FORMAT=<ID=GT,Number=1,Type=String,Description=\"Genotype\">\n\
+             ##FORMAT=<ID=PS,Number=1,Type=Integer,Description=\"Phase set\">\n\
+             #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tS1\tS2\n\
+             chr7\t152800000\t.\tA\tG\t.\tPASS\t.\tGT:PS\t0|1:152800000\t1|0:152800000\n\
+             chr7\t152900000\t.\tC\tT\t.\tPASS\t.\tGT:PS\t1|0:152800000\t0|1:152800000\n"
+        )
+        .unwrap();
+    }
+    let c_vcf = std::ffi::CString::new(vcf).unwrap();
+    assert_eq!(
+        unsafe {
+            rust_htslib::htslib::tbx_index_build(
+                c_vcf.as_ptr(),
+                0,
+                &rust_htslib::htslib::tbx_conf_vcf,
+            )
+        },
+        0
+    );
+    for (samples, expected) in [
+        (vec!["S2"], vec!["S2"]),
+        // a sample that is not in the vcf is skipped with a warning
+        (vec!["S2", "S3", "S1"], vec!["S2", "S1"]),
+    ] {
+        let args = Cli {
+            input: vec![PathBuf::from(vcf)],
+            output: "test_samples.html".to_string(),
+            summary: Some("test_samples_summary.txt".to_string()),
+            samples: Some(samples.iter().map(|s| s.to_string()).collect()),
+            ..test_cli()
+        };
+        assert_eq!(run_phasius(args), 0);
+        let summary = std::fs::read_to_string("test_samples_summary.txt").unwrap();
+        let tracks: Vec<&str> = summary
+            .lines()
+            .skip(1)
+            .map(|line| line.split('\t').next().unwrap())
+            .collect();
+        assert_eq!(tracks, expected);
+        assert!(summary
+            .lines()
+            .skip(1)
+            .all(|line| line.contains("\t152799999-152900000\t")));
+    }
+    std::fs::remove_file(vcf).unwrap();
+    std::fs::remove_file(format!("{}.tbi", vcf)).unwrap();
+    std::fs::remove_file("test_samples_summary.txt").unwrap();
 }
 
 #[test]
 fn run_with_strict() {
-    let test_cli = Cli {
+    let args = Cli {
+        output: "test_strict.html".to_string(),
+        region: Some("chr7:152800000-156700000".to_string()),
+        strict: true,
+        ..test_cli()
+    };
+    run_phasius(args);
+}
+
+#[test]
+fn run_without_strict() {
+    let args = Cli {
+        output: "test_without_strict.html".to_string(),
+        region: Some("chr7:152800000-156700000".to_string()),
+        ..test_cli()
+    };
+    run_phasius(args);
+}
+
+#[test]
+fn run_with_sample_sheet() {
+    // paths in the sample sheet are relative to its location
+    std::fs::write(
+        "test-data/test_sample_sheet.tsv",
+        "sample_id\tpath\tgroup\tsex\nsample1\tsmall-test-phased.bam\tcases\tF\nsample2\tsmall-test-phased.bam\tcontrols\tM\n",
+    )
+    .expect("Unable to write file");
+    let args = Cli {
+        input: vec![],
+        sample_sheet: Some(PathBuf::from("test-data/test_sample_sheet.tsv")),
+        output: "test_sample_sheet.html".to_string(),
+        summary: Some("test_sample_sheet_summary.txt".to_string()),
+        ..test_cli()
+    };
+    run_phasius(args);
+    let summary = std::fs::read_to_string("test_sample_sheet_summary.txt").unwrap();
+    assert!(summary
+        .starts_with("sample_name\tnum_blocks\tblock_coordinates\tnum_overlapping\tphasesets\tsupport\trecords\tgroup\tsex\n"));
+    assert!(summary.contains("\nsample2\t"));
+}
+
+#[test]
+fn test_labelled_output_name() {
+    assert_eq!(labelled_output_name("out.html", "chr1"), "out.chr1.html");
+    assert_eq!(
+        labelled_output_name("maps/out.html", "chrX"),
+        "maps/out.chrX.html"
+    );
+    assert_eq!(labelled_output_name("out", "chr1"), "out.chr1");
+}
+
+#[test]
+fn run_whole_contig() {
+    let args = Cli {
+        input: vec![PathBuf::from("test-data/small-test-phased.bam")],
+        output: "test_whole_contig.html".to_string(),
+        region: Some("chr7".to_string()),
+        strict: true,
+        ..test_cli()
+    };
+    run_phasius(args);
+    assert!(std::path::Path::new("test_whole_contig.html").exists());
+}
+
+#[test]
+fn run_genome_wide() {
+    let args = Cli {
+        input: vec![PathBuf::from("test-data/small-test-phased.bam")],
+        output: "test_genome_wide.html".to_string(),
+        region: None,
+        summary: Some("test_genome_wide_summary.txt".to_string()),
+        ..test_cli()
+    };
+    run_phasius(args);
+    assert!(std::path::Path::new("test_genome_wide.chr7.html").exists());
+    assert!(std::path::Path::new("test_genome_wide_summary.chr7.txt").exists());
+}
+
+#[test]
+fn run_whole_contig_different_builds() {
+    use rust_htslib::bam::{self, Read};
+    // a copy of the chr7 reads of the test bam, with chr7 shorter as in another build
+    let mut reader = bam::Reader::from_path("test-data/small-test-phased.bam").unwrap();
+    let tid = reader.header().tid(b"chr7").unwrap();
+    let mut header = bam::Header::new();
+    header.push_record(
+        bam::header::HeaderRecord::new(b"SQ")
+            .push_tag(b"SN", "chr7")
+            .push_tag(b"LN", 159138663),
+    );
+    {
+        let mut writer =
+            bam::Writer::from_path("test-data/other-build.bam", &header, bam::Format::Bam).unwrap();
+        for read in reader.records() {
+            let mut read = read.unwrap();
+            if read.tid() == tid as i32 {
+                read.set_tid(0);
+                read.set_mtid(-1);
+                writer.write(&read).unwrap();
+            }
+        }
+    }
+    bam::index::build("test-data/other-build.bam", None, bam::index::Type::Bai, 1).unwrap();
+    let args = Cli {
         input: vec![
             PathBuf::from("test-data/small-test-phased.bam"),
+            PathBuf::from("test-data/other-build.bam"),
+        ],
+        output: "test_other_build.html".to_string(),
+        region: Some("chr7".to_string()),
+        ..test_cli()
+    };
+    // the whole contig is not out of bounds in the shorter input, the length mismatch is a warning
+    assert_eq!(run_phasius(args), 0);
+    std::fs::remove_file("test-data/other-build.bam").unwrap();
+    std::fs::remove_file("test-data/other-build.bam.bai").unwrap();
+    std::fs::remove_file("test_other_build.html").unwrap();
+}
+
+#[test]
+fn run_genome_wide_different_contigs() {
+    use rust_htslib::bam::{self, Read};
+    // a copy of the chr7 reads of the test bam, with a decoy contig that the test bam lacks
+    let mut reader = bam::Reader::from_path("test-data/small-test-phased.bam").unwrap();
+    let tid = reader.header().tid(b"chr7").unwrap();
+    let mut header = bam::Header::new();
+    for (name, length) in [
+        ("chr7", reader.header().target_len(tid).unwrap()),
+        ("chrUn_decoy", 100000),
+    ] {
+        header.push_record(
+            bam::header::HeaderRecord::new(b"SQ")
+                .push_tag(b"SN", name)
+                .push_tag(b"LN", length),
+        );
+    }
+    {
+        let mut writer =
+            bam::Writer::from_path("test-data/with-decoy.bam", &header, bam::Format::Bam).unwrap();
+        for read in reader.records() {
+            let mut read = read.unwrap();
+            if read.tid() == tid as i32 {
+                read.set_tid(0);
+                read.set_mtid(-1);
+                writer.write(&read).unwrap();
+            }
+        }
+    }
+    bam::index::build("test-data/with-decoy.bam", None, bam::index::Type::Bai, 1).unwrap();
+    let args = Cli {
+        input: vec![
             PathBuf::from("test-data/small-test-phased.bam"),
-            PathBuf::from("test-data/small-test-phased.bam"),
+            PathBuf::from("test-data/with-decoy.bam"),
         ],
-        bed: None,
-        threads: 2,
-        decompression: 1,
-        output: "test_strict.html".to_string(),
-        region: "chr7:152800000-156700000".to_string(),
-        width: None,
-        summary: None,
-        strict: true,
+        output: "test_different_contigs.html".to_string(),
+        region: None,
+        ..test_cli()
     };
-    run_phasius(test_cli);
+    // the test bam is an empty track on the decoy, rather than a failure
+    assert_eq!(run_phasius(args), 0);
+    assert!(std::path::Path::new("test_different_contigs.chr7.html").exists());
+    std::fs::remove_file("test-data/with-decoy.bam").unwrap();
+    std::fs::remove_file("test-data/with-decoy.bam.bai").unwrap();
 }
 
 #[test]
-fn run_without_strict() {
-    let test_cli = Cli {
+fn run_with_explicit_index() {
+    // a copy of the test bam without an index next to it
+    std::fs::copy(
+        "test-data/small-test-phased.bam",
+        "test-data/explicit-index.bam",
+    )
+    .unwrap();
+    let input = "test-data/explicit-index.bam##idx##test-data/small-test-phased.bam.bai";
+    let args = Cli::try_parse_from([
+        "phasius",
+        "--output",
+        "test_explicit_index.html",
+        "--region",
+        "chr7:152743763-156779243",
+        input,
+    ])
+    .unwrap();
+    assert_eq!(run_phasius(args), 0);
+    // in a sample sheet, the file and the index are relative to the sample sheet
+    std::fs::write(
+        "test-data/test_explicit_index.tsv",
+        "sample_id\tpath\nsample1\texplicit-index.bam##idx##small-test-phased.bam.bai\n",
+    )
+    .unwrap();
+    let args = Cli::try_parse_from([
+        "phasius",
+        "--output",
+        "test_explicit_index.html",
+        "--region",
+        "chr7:152743763-156779243",
+        "--sample-sheet",
+        "test-data/test_explicit_index.tsv",
+    ])
+    .unwrap();
+    assert_eq!(run_phasius(args), 0);
+    std::fs::remove_file("test-data/explicit-index.bam").unwrap();
+    std::fs::remove_file("test-data/test_explicit_index.tsv").unwrap();
+}
+
+#[test]
+fn run_with_regions() {
+    std::fs::write(
+        "test-data/test_regions.bed",
+        "chr7\t152743763\t154000000\tGENE1\nchr7\t154000000\t156779243\n",
+    )
+    .expect("Unable to write file");
+    let args = Cli {
         input: vec![
             PathBuf::from("test-data/small-test-phased.bam"),
             PathBuf::from("test-data/small-test-phased.bam"),
+        ],
+        output: "test_regions.html".to_string(),
+        region: None,
+        regions: Some(PathBuf::from("test-data/test_regions.bed")),
+        blocks_bed: Some(PathBuf::from("test_regions.bed.gz")),
+        ..test_cli()
+    };
+    run_phasius(args);
+    assert!(std::path::Path::new("test_regions.GENE1.html").exists());
+    assert!(std::path::Path::new("test_regions.chr7_154000000-156779243.html").exists());
+    assert!(std::path::Path::new("test_regions.bed.gz.tbi").exists());
+    let index = std::fs::read_to_string("test_regions.html").unwrap();
+    assert!(index.contains("<a href=\"test_regions.GENE1.html\">GENE1</a>"));
+}
+
+#[test]
+fn run_with_keep_going() {
+    let args = Cli {
+        input: vec![
             PathBuf::from("test-data/small-test-phased.bam"),
+            PathBuf::from("test-data/small-test-phased.bam.bai"),
         ],
-        bed: None,
-        threads: 2,
-        decompression: 1,
-        output: "test_without_strict.html".to_string(),
-        region: "chr7:152800000-156700000".to_string(),
-        width: None,
-        summary: None,
-        strict: false,
+        output: "test_keep_going.html".to_string(),
+        summary: Some("test_keep_going_summary.txt".to_string()),
+        keep_going: true,
+        ..test_cli()
     };
-    run_phasius(test_cli);
+    // the index is not a supported input
+    assert_eq!(run_phasius(args), 1);
+    let summary = std::fs::read_to_string("test_keep_going_summary.txt").unwrap();
+    assert!(summary.contains("\tfailed: Unsupported file format"));
 }
 
 #[test]
 fn run_with_empty_blocks() {
     use crate::blocks::Blocks;
 
-    let test_cli = Cli {
+    let args = Cli {
         input: vec![
             PathBuf::from("test-data/small-test-phased.bam"),
             PathBuf::from("test-data/small-test-phased.bam"),
         ],
-        bed: None,
-        threads: 2,
-        decompression: 1,
         output: "test_with_empty_blocks.html".to_string(),
-        region: "chr7:152800000-156700000".to_string(),
-        width: None,
+        region: Some("chr7:152800000-156700000".to_string()),
         summary: Some("test_empty_blocks_summary.txt".to_string()),
-        strict: false,
+        ..test_cli()
     };
 
     // Extract blocks from BAM files
-    let target = utils::process_region(&test_cli.region).expect("Error: Improper interval!");
-    let mut blocks_per_bam = extract_blocks(&test_cli, &target);
+    let target = utils::process_region(args.region.as_ref().unwrap(), false)
+        .expect("Error: Improper interval!");
+    let mut blocks_per_bam = extract_blocks(&args, std::slice::from_ref(&target), None).remove(0);
 
     // Add a single empty block
     blocks_per_bam.push(vec![Blocks {
@@ -345,14 +1396,21 @@ fn run_with_empty_blocks() {
         end: 0,
         name: "test-data/empty-test.bam".to_string(),
         empty: true,
+        ..Default::default()
     }]);
 
     // Test plotting
-    plot_blocks(&blocks_per_bam, &test_cli, target);
+    plot_blocks(
+        &blocks_per_bam,
+        &args,
+        &target,
+        &args.output,
+        &aliases::ContigAliases::default(),
+    );
 
     // Test summarizing
     let summary_per_sample = summary::summarize(&blocks_per_bam);
-    std::fs::write(test_cli.summary.unwrap(), summary_per_sample).expect("Unable to write file");
+    std::fs::write(args.summary.unwrap(), summary_per_sample).expect("Unable to write file");
 
     // Verify the summary file was created
     assert!(std::path::Path::new("test_empty_blocks_summary.txt").exists());
@@ -0,0 +1,454 @@
+use crate::aliases::ContigAliases;
+use crate::cache::BlockCache;
+use crate::error::PhasiusError;
+use crate::extract::{self, Contigs, ExtractOptions, InputFormat};
+use crate::utils::Reg;
+use crate::vcf::IndexedVcf;
+use rayon::prelude::*;
+use rust_htslib::bam::Read;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Check all inputs before extraction starts: whether their format is supported and their index can be read,
+/// and if check_regions is set, whether the regions are on a contig in the file and within its length.
+/// A region is only out of bounds if it extends beyond the contig in all inputs: a contig that is shorter
+/// in some inputs points to different reference builds, which check_lengths reports.
+/// All problems are returned, rather than only the first, together with the contigs read from the header
+/// of each alignment and variant file, to compare their lengths with check_lengths.
+/// Inputs of which the contigs are in the cache passed the checks in an earlier run, and are not opened again
+/// if their blocks in all regions are cached too, for the extraction options the cache is used with.
+pub fn check_inputs(
+    paths: &[PathBuf],
+    regions: &[Reg],
+    check_regions: bool,
+    aliases: &ContigAliases,
+    threads: usize,
+    cache: Option<(&BlockCache, &ExtractOptions)>,
+) -> (Vec<(PathBuf, Contigs)>, Vec<PhasiusError>) {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap();
+    let checked: Vec<(Option<Contigs>, Vec<PhasiusError>)> = pool.install(|| {
+        paths
+            .par_iter()
+            .map(|path| check_input(path, regions, check_regions, aliases, cache))
+            .collect()
+    });
+    let mut headers = vec![];
+    let mut problems = vec![];
+    for (path, (contigs, file_problems)) in paths.iter().zip(checked) {
+        if let Some(contigs) = contigs {
+            headers.push((path.clone(), contigs));
+        }
+        problems.extend(file_problems);
+    }
+    if check_regions {
+        problems.extend(check_bounds(&headers, regions, aliases));
+    }
+    (headers, problems)
+}
+
+fn check_input(
+    path: &Path,
+    regions: &[Reg],
+    check_regions: bool,
+    aliases: &ContigAliases,
+    cache: Option<(&BlockCache, &ExtractOptions)>,
+) -> (Option<Contigs>, Vec<PhasiusError>) {
+    // an input that is not fully cached is opened for extraction, and its index is checked here
+    let cached = cache.and_then(|(cache, options)| {
+        let contigs = cache.get_contigs(path)?;
+        cache.has_blocks(path, options, regions).then_some(contigs)
+    });
+    let contigs = match cached {
+        Some(contigs) => contigs,
+        None => match read_header(path) {
+            Ok(Some(contigs)) => {
+                if let Some((cache, _)) = cache {
+                    cache.store_contigs(path, &contigs);
+                }
+                contigs
+            }
+            Ok(None) => return (None, vec![]),
+            Err(e) => return (None, vec![e]),
+        },
+    };
+    let problems = if check_regions {
+        check_contigs(path, &contigs, regions, aliases)
+    } else {
+        vec![]
+    };
+    (Some(contigs), problems)
+}
+
+/// The contigs of an alignment or variant file, or None for a block list, which has no index
+/// and only lists the contigs with blocks.
+/// Opening an indexed reader loads the index (also when given as file##idx##index) and the header.
+fn read_header(path: &Path) -> Result<Option<Contigs>, PhasiusError> {
+    match extract::detect_format(path)? {
+        InputFormat::Alignment => {
+            let bam = extract::open_indexed_bam(path)?;
+            Ok(Some(extract::bam_contigs(bam.header())))
+        }
+        InputFormat::Variant => {
+            let vcf = IndexedVcf::from_path(path)?;
+            Ok(Some(extract::vcf_contigs(vcf.header())))
+        }
+        InputFormat::BlockList(_) => Ok(None),
+    }
+}
+
+fn check_contigs(
+    path: &Path,
+    contigs: &Contigs,
+    regions: &[Reg],
+    aliases: &ContigAliases,
+) -> Vec<PhasiusError> {
+    regions
+        .iter()
+        .filter(|region| contig_length(contigs, &region.chrom, aliases).is_none())
+        .map(|region| PhasiusError::ContigNotFound {
+            contig: region.chrom.clone(),
+            file: path.to_path_buf(),
+        })
+        .collect()
+}
+
+/// the length of a contig in the contigs of a file, None if the contig is not in the file
+/// and Some(None) if its length is unknown
+fn contig_length(contigs: &Contigs, chrom: &str, aliases: &ContigAliases) -> Option<Option<u64>> {
+    let chrom = aliases.resolve(chrom, |c| contigs.iter().any(|(n, _)| n == c))?;
+    contigs
+        .iter()
+        .find(|(n, _)| *n == chrom)
+        .map(|(_, length)| *length)
+}
+
+/// Report the regions that extend beyond the end of their contig in all headers.
+/// The ends of whole contigs are set to the largest length in the inputs, and are never out of bounds.
+fn check_bounds(
+    headers: &[(PathBuf, Contigs)],
+    regions: &[Reg],
+    aliases: &ContigAliases,
+) -> Vec<PhasiusError> {
+    let mut problems = vec![];
+    for region in regions {
+        let lengths: Vec<(&PathBuf, u64)> = headers
+            .iter()
+            .filter_map(|(path, contigs)| {
+                Some((path, contig_length(contigs, &region.chrom, aliases)??))
+            })
+            .collect();
+        if lengths
+            .iter()
+            .any(|(_, length)| region.end as u64 <= *length)
+        {
+            continue;
+        }
+        problems.extend(lengths.into_iter().map(|(path, length)| {
+            PhasiusError::RegionOutOfBounds {
+                region: region.to_string(),
+                length,
+                file: path.clone(),
+            }
+        }));
+    }
+    problems
+}
+
+/// Compare the lengths of the contigs of the regions in the headers returned by check_inputs,
+/// and in the fasta index if given, to catch inputs from different reference builds.
+/// Block lists have no contig lengths, and are not part of the headers.
+pub fn check_lengths(
+    headers: &[(PathBuf, Contigs)],
+    regions: &[Reg],
+    aliases: &ContigAliases,
+    fai: Option<&Path>,
+) -> Vec<PhasiusError> {
+    let fai_contigs = match fai.map(read_fai).transpose() {
+        Ok(fai_contigs) => fai_contigs,
+        Err(e) => return vec![e],
+    };
+    let headers: Vec<(&Path, &Contigs)> = headers
+        .iter()
+        .map(|(path, contigs)| (path.as_path(), contigs))
+        .chain(fai.zip(fai_contigs.as_ref()))
+        .collect();
+    compare_lengths(&headers, regions, aliases)
+}
+
+fn compare_lengths(
+    headers: &[(&Path, &Contigs)],
+    regions: &[Reg],
+    aliases: &ContigAliases,
+) -> Vec<PhasiusError> {
+    let lengths_per_file: Vec<(&Path, HashMap<&str, u64>)> = headers
+        .iter()
+        .map(|(path, contigs)| {
+            let lengths = contigs
+                .iter()
+                .filter_map(|(chrom, length)| Some((chrom.as_str(), (*length)?)))
+                .collect();
+            (*path, lengths)
+        })
+        .collect();
+    let mut checked = HashSet::new();
+    let mut problems = vec![];
+    for region in regions {
+        if !checked.insert(region.chrom.as_str()) {
+            continue;
+        }
+        let mut lengths: Vec<(u64, Vec<PathBuf>)> = vec![];
+        for (path, file_lengths) in &lengths_per_file {
+            let Some(length) = aliases
+                .resolve(&region.chrom, |c| file_lengths.contains_key(c))
+                .map(|chrom| file_lengths[chrom.as_str()])
+            else {
+                continue;
+            };
+            match lengths.iter_mut().find(|(l, _)| *l == length) {
+                Some((_, files)) => files.push(path.to_path_buf()),
+                None => lengths.push((length, vec![path.to_path_buf()])),
+            }
+        }
+        if lengths.len() > 1 {
+            problems.push(PhasiusError::LengthMismatch {
+                contig: region.chrom.clone(),
+                lengths,
+            });
+        }
+    }
+    problems
+}
+
+/// the contig names and lengths of a fasta index (.fai)
+fn read_fai(path: &Path) -> Result<Contigs, PhasiusError> {
+    let content =
+        std::fs::read_to_string(path).map_err(|_| PhasiusError::Open(path.to_path_buf()))?;
+    content
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let mut fields = line.split('\t');
+            match (fields.next(), fields.next().and_then(|l| l.parse().ok())) {
+                (Some(chrom), Some(length)) => Ok((chrom.to_string(), Some(length))),
+                _ => Err(PhasiusError::Parse {
+                    file: path.to_path_buf(),
+                    message: format!("invalid fasta index line: {}", line),
+                }),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(chrom: &str, start: u32, end: u32) -> Reg {
+        Reg {
+            chrom: chrom.to_string(),
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn test_check_contigs() {
+        // the regions are on chr7 and chr8, named differently in the file
+        let contigs = vec![("7".to_string(), Some(1000)), ("chr8".to_string(), None)];
+        let regions = [
+            region("chr7", 100, 200),
+            region("chr7", 900, 1100),
+            region("chr8", 100, 200),
+            region("chr9", 100, 200),
+        ];
+        let problems = check_contigs(
+            Path::new("x.bam"),
+            &contigs,
+            &regions,
+            &ContigAliases::default(),
+        );
+        assert!(matches!(
+            problems[..],
+            [PhasiusError::ContigNotFound { .. }]
+        ));
+    }
+
+    #[test]
+    fn test_check_bounds() {
+        // chr7 is shorter in b.bam, as with inputs from different builds
+        let headers = [
+            (
+                PathBuf::from("a.bam"),
+                vec![("chr7".to_string(), Some(1000))],
+            ),
+            (PathBuf::from("b.bam"), vec![("7".to_string(), Some(900))]),
+        ];
+        let regions = [
+            region("chr7", 100, 200),
+            region("chr7", 0, 1000),
+            region("chr7", 900, 1100),
+        ];
+        let problems = check_bounds(&headers, &regions, &ContigAliases::default());
+        assert_eq!(problems.len(), 2);
+        assert!(problems
+            .iter()
+            .all(|problem| matches!(problem, PhasiusError::RegionOutOfBounds { region, .. } if region == "chr7:900-1100")));
+    }
+
+    #[test]
+    fn test_check_inputs() {
+        let regions = [region("chr7", 152743763, 156779243)];
+        let (headers, problems) = check_inputs(
+            &[
+                PathBuf::from("test-data/small-test-phased.bam"),
+                PathBuf::from("test-data/small-test-phased.bam.bai"),
+                PathBuf::from("test-data/missing.bam"),
+            ],
+            &regions,
+            true,
+            &ContigAliases::default(),
+            2,
+            None,
+        );
+        assert_eq!(problems.len(), 2);
+        assert!(matches!(problems[0], PhasiusError::UnsupportedFormat(_)));
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers[0].1[0].0, "chr7");
+        assert!(matches!(problems[1], PhasiusError::Open(_)));
+    }
+
+    #[test]
+    fn test_check_inputs_index() {
+        let regions = [region("chr7", 152743763, 156779243)];
+        std::fs::copy("test-data/small-test-phased.bam", "test-data/preflight.bam").unwrap();
+        let (_, problems) = check_inputs(
+            &[PathBuf::from("test-data/preflight.bam")],
+            &regions,
+            true,
+            &ContigAliases::default(),
+            1,
+            None,
+        );
+        assert!(matches!(problems[..], [PhasiusError::MissingIndex(_)]));
+        // an explicit index
+        let (_, problems) = check_inputs(
+            &[PathBuf::from(
+                "test-data/preflight.bam##idx##test-data/small-test-phased.bam.bai",
+            )],
+            &regions,
+            true,
+            &ContigAliases::default(),
+            1,
+            None,
+        );
+        assert!(problems.is_empty());
+        std::fs::remove_file("test-data/preflight.bam").unwrap();
+    }
+
+    #[test]
+    fn test_check_inputs_cache() {
+        let cached = region("chr7", 152743763, 156779243);
+        let other = region("chr7", 152743763, 154000000);
+        let bam = PathBuf::from("test-data/preflight-cached.bam");
+        std::fs::copy("test-data/small-test-phased.bam", &bam).unwrap();
+        std::fs::copy(
+            "test-data/small-test-phased.bam.bai",
+            "test-data/preflight-cached.bam.bai",
+        )
+        .unwrap();
+        let cache = BlockCache::new(Path::new("test-data/preflight-cache")).unwrap();
+        let options = ExtractOptions {
+            threads: 1,
+            ..Default::default()
+        };
+        let check = |regions: &[Reg]| {
+            check_inputs(
+                std::slice::from_ref(&bam),
+                regions,
+                true,
+                &ContigAliases::default(),
+                1,
+                Some((&cache, &options)),
+            )
+            .1
+        };
+        assert!(check(std::slice::from_ref(&cached)).is_empty());
+        cache
+            .get_blocks(&bam, &options, std::slice::from_ref(&cached))
+            .unwrap();
+        std::fs::remove_file("test-data/preflight-cached.bam.bai").unwrap();
+        // the blocks are cached, so the input is not opened
+        assert!(check(std::slice::from_ref(&cached)).is_empty());
+        // the other region has to be extracted, which needs the index
+        let problems = check(&[cached, other]);
+        std::fs::remove_file(&bam).unwrap();
+        std::fs::remove_dir_all("test-data/preflight-cache").unwrap();
+        assert!(matches!(problems[..], [PhasiusError::MissingIndex(_)]));
+    }
+
+    #[test]
+    fn test_compare_lengths() {
+        let headers = [
+            (
+                PathBuf::from("a.bam"),
+                vec![
+                    ("chr7".to_string(), Some(159345973)),
+                    ("chr8".to_string(), Some(145138636)),
+                ],
+            ),
+            (
+                PathBuf::from("b.bam"),
+                vec![
+                    ("7".to_string(), Some(159138663)),
+                    ("8".to_string(), Some(145138636)),
+                ],
+            ),
+            (
+                PathBuf::from("c.bam"),
+                vec![("chr7".to_string(), Some(159345973))],
+            ),
+        ];
+        let regions = [
+            region("chr7", 100, 200),
+            region("chr7", 300, 400),
+            region("chr8", 100, 200),
+        ];
+        let headers: Vec<(&Path, &Contigs)> = headers
+            .iter()
+            .map(|(path, contigs)| (path.as_path(), contigs))
+            .collect();
+        let problems = compare_lengths(&headers, &regions, &ContigAliases::default());
+        assert_eq!(problems.len(), 1);
+        assert_eq!(
+            problems[0].to_string(),
+            "Contig chr7 has a different length in the inputs, suggesting different reference builds: \
+             159345973 in a.bam, c.bam; 159138663 in b.bam"
+        );
+    }
+
+    #[test]
+    fn test_check_lengths_fai() {
+        let regions = [region("chr7", 152743763, 156779243)];
+        let aliases = ContigAliases::default();
+        let bam = [PathBuf::from("test-data/small-test-phased.bam")];
+        let (headers, _) = check_inputs(&bam, &regions, true, &aliases, 1, None);
+        let length = headers[0].1[0].1.unwrap();
+        std::fs::write(
+            "test-data/lengths.fa.fai",
+            format!("chr7\t{}\t6\t60\t61\n", length),
+        )
+        .unwrap();
+        let fai = Path::new("test-data/lengths.fa.fai");
+        assert!(check_lengths(&headers, &regions, &aliases, Some(fai)).is_empty());
+        std::fs::write("test-data/lengths.fa.fai", "chr7\t1000\t6\t60\t61\n").unwrap();
+        let problems = check_lengths(&headers, &regions, &aliases, Some(fai));
+        assert!(matches!(
+            problems[..],
+            [PhasiusError::LengthMismatch { .. }]
+        ));
+        std::fs::remove_file(fai).unwrap();
+    }
+}
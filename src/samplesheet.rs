@@ -0,0 +1,189 @@
+use crate::blocks::Blocks;
+use crate::utils::split_explicit_index;
+use std::path::{Path, PathBuf};
+
+/// An input file, with the sample information from the sample sheet if one was used
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub id: Option<String>,
+    pub path: PathBuf,
+    pub group: Option<String>,
+    pub metadata: Vec<(String, String)>,
+}
+
+impl Sample {
+    /// An input file given on the command line, named after the file itself
+    pub fn from_path(path: PathBuf) -> Sample {
+        Sample {
+            id: None,
+            path,
+            group: None,
+            metadata: vec![],
+        }
+    }
+
+    /// Apply the sample information to the tracks extracted from its file.
+    /// From a multi-sample VCF, only the track of this sample is kept if it is present.
+    pub fn label(&self, tracks: Vec<Vec<Blocks>>) -> Vec<Vec<Blocks>> {
+        let tracks = match &self.id {
+            Some(id) if tracks.len() > 1 => {
+                if tracks.iter().any(|track| &track[0].name == id) {
+                    tracks
+                        .into_iter()
+                        .filter(|track| &track[0].name == id)
+                        .collect()
+                } else {
+                    eprintln!(
+                        "Warning: Sample {} not found in {}, keeping all its samples",
+                        id,
+                        self.path.display()
+                    );
+                    tracks
+                }
+            }
+            _ => tracks,
+        };
+        let single_track = tracks.len() == 1;
+        tracks
+            .into_iter()
+            .map(|track| {
+                track
+                    .into_iter()
+                    .map(|block| Blocks {
+                        name: match &self.id {
+                            Some(id) if single_track => id.clone(),
+                            _ => block.name,
+                        },
+                        group: self.group.clone(),
+                        metadata: self.metadata.clone(),
+                        ..block
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// parse a tab-separated sample sheet with a header line, containing at least the columns sample_id and path,
+/// and optionally group. All other columns are kept as metadata.
+/// Relative paths are interpreted relative to the directory of the sample sheet.
+pub fn parse_sample_sheet(p: &Path) -> Result<Vec<Sample>, Box<dyn std::error::Error>> {
+    let content = std::fs::read_to_string(p)?;
+    parse_samples(&content, p.parent().unwrap_or(Path::new("")))
+}
+
+fn parse_samples(content: &str, base: &Path) -> Result<Vec<Sample>, Box<dyn std::error::Error>> {
+    let mut lines = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+    let (_, header) = lines.next().ok_or("Sample sheet is empty")?;
+    let columns: Vec<&str> = header.trim_start_matches('#').split('\t').collect();
+    let id_column = columns
+        .iter()
+        .position(|c| *c == "sample_id")
+        .ok_or("Sample sheet lacks a sample_id column")?;
+    let path_column = columns
+        .iter()
+        .position(|c| *c == "path")
+        .ok_or("Sample sheet lacks a path column")?;
+    let group_column = columns.iter().position(|c| *c == "group");
+
+    let mut samples = vec![];
+    for (index, line) in lines {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != columns.len() {
+            return Err(format!(
+                "Line {} of the sample sheet has {} columns, expected {}",
+                index + 1,
+                fields.len(),
+                columns.len()
+            )
+            .into());
+        }
+        // with an explicit index as file##idx##index, both are relative to the sample sheet
+        let path = match split_explicit_index(Path::new(fields[path_column])) {
+            (file, Some(index)) => PathBuf::from(format!(
+                "{}##idx##{}",
+                base.join(file).display(),
+                base.join(index).display()
+            )),
+            (file, None) => base.join(file),
+        };
+        if !split_explicit_index(&path).0.is_file() {
+            return Err(format!(
+                "File '{}' of sample {} does not exist or is not a file",
+                path.display(),
+                fields[id_column]
+            )
+            .into());
+        }
+        let metadata = columns
+            .iter()
+            .zip(fields.iter())
+            .enumerate()
+            .filter(|(i, _)| *i != id_column && *i != path_column && Some(*i) != group_column)
+            .map(|(_, (column, value))| (column.to_string(), value.to_string()))
+            .collect();
+        samples.push(Sample {
+            id: Some(fields[id_column].to_string()),
+            path,
+            group: group_column.map(|i| fields[i].to_string()),
+            metadata,
+        });
+    }
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_samples() {
+        let content =
+            "sample_id\tpath\tgroup\tsex\nS1\tCargo.toml\tcases\tF\n\nS2\tREADME.md\tcontrols\tM\n";
+        let samples = parse_samples(content, Path::new("")).unwrap();
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].id.as_deref(), Some("S1"));
+        assert_eq!(samples[0].path, PathBuf::from("Cargo.toml"));
+        assert_eq!(samples[1].group.as_deref(), Some("controls"));
+        assert_eq!(
+            samples[1].metadata,
+            vec![("sex".to_string(), "M".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_parse_samples_errors() {
+        assert!(parse_samples("path\tgroup\nCargo.toml\tcases\n", Path::new("")).is_err());
+        assert!(parse_samples("sample_id\tpath\nS1\n", Path::new("")).is_err());
+        assert!(parse_samples("sample_id\tpath\nS1\tmissing.bam\n", Path::new("")).is_err());
+    }
+
+    #[test]
+    fn test_label() {
+        let track = |name: &str| {
+            vec![Blocks {
+                start: 1,
+                end: 2,
+                name: name.to_string(),
+                ..Default::default()
+            }]
+        };
+        let sample = Sample {
+            id: Some("S2".to_string()),
+            path: PathBuf::from("cohort.vcf.gz"),
+            group: Some("cases".to_string()),
+            metadata: vec![],
+        };
+        let tracks = sample.label(vec![track("S1"), track("S2")]);
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0][0].name, "S2");
+        assert_eq!(tracks[0][0].group.as_deref(), Some("cases"));
+        let tracks = sample.label(vec![track("barcode123")]);
+        assert_eq!(tracks[0][0].name, "S2");
+        let tracks = Sample::from_path(PathBuf::from("x.bam")).label(vec![track("x")]);
+        assert_eq!(tracks[0][0].name, "x");
+    }
+}
@@ -1,31 +1,210 @@
 use crate::blocks::Blocks;
+use crate::utils::Reg;
 
 // for each sample, write one line per block specifying its name, the number of blocks, and a list of blocks
 // with their start and end positions
 // the end result is a tab-separated file with the following format:
-// Sample_name\tnum_blocks\tstart1-end1;start2-end2;...;startN-endN\n
+// Sample_name\tnum_blocks\tstart1-end1;start2-end2;...;startN-endN\tnum_overlapping\tphasesets\tsupport\trecords\n
+// where num_overlapping is the number of blocks that overlap with a block of another phase set,
+// and phasesets, support and records list the phase set, number of supporting reads/variants and
+// the start of the first and last of these (first-last) in the same order as the blocks
+// if extraction failed for some samples (with --keep-going), a status column lists "ok" or the error
+// if a sample sheet was used, its group and metadata columns are added at the end of each line
 pub fn summarize(blocks: &[Vec<Blocks>]) -> String {
     let mut summary = String::new();
+    let columns = SampleColumns::new(blocks, true);
 
     // Add header line
-    summary.push_str("sample_name\tnum_blocks\tblock_coordinates\n");
+    summary.push_str(
+        "sample_name\tnum_blocks\tblock_coordinates\tnum_overlapping\tphasesets\tsupport\trecords",
+    );
+    summary.push_str(&columns.header());
+    summary.push('\n');
 
     for blocks in blocks.iter() {
         let name = String::from(&blocks[0].name);
+        let sample_columns = columns.values(blocks);
         if blocks[0].empty {
-            summary.push_str(&format!("{}\t0\t0\n", name));
+            summary.push_str(&format!("{}\t0\t0\t0\t0\t0\t0{}\n", name, sample_columns));
             continue;
         }
         let num_blocks = blocks.len();
-        let blocks = blocks
-            .iter()
-            .map(|block| format!("{}-{}", block.start, block.end))
-            .collect::<Vec<String>>()
-            .join(";");
-        summary.push_str(&format!("{}\t{}\t{}\n", name, num_blocks, blocks));
+        let num_overlapping = blocks.iter().filter(|block| block.overlapping).count();
+        let join = |field: &dyn Fn(&Blocks) -> String| {
+            blocks.iter().map(field).collect::<Vec<String>>().join(";")
+        };
+        summary.push_str(&format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}{}\n",
+            name,
+            num_blocks,
+            join(&|block| format!("{}-{}", block.start, block.end)),
+            num_overlapping,
+            join(&|block| block.phaseset.to_string()),
+            join(&|block| block.support.to_string()),
+            join(&|block| format!("{}-{}", block.first_record, block.last_record)),
+            sample_columns
+        ));
     }
     summary
 }
+
+/// The status, group and sample sheet columns added after the columns of a table, if any sample has them
+struct SampleColumns<'a> {
+    status: bool,
+    group: bool,
+    metadata: Vec<&'a str>,
+}
+
+impl<'a> SampleColumns<'a> {
+    /// with_status adds the status column if extraction failed for any sample
+    fn new(blocks: &'a [Vec<Blocks>], with_status: bool) -> SampleColumns<'a> {
+        let mut metadata: Vec<&str> = vec![];
+        for blocks in blocks.iter() {
+            for (column, _) in &blocks[0].metadata {
+                if !metadata.contains(&column.as_str()) {
+                    metadata.push(column);
+                }
+            }
+        }
+        SampleColumns {
+            status: with_status && blocks.iter().any(|blocks| blocks[0].error.is_some()),
+            group: blocks.iter().any(|blocks| blocks[0].group.is_some()),
+            metadata,
+        }
+    }
+
+    fn header(&self) -> String {
+        let mut header = String::new();
+        if self.status {
+            header.push_str("\tstatus");
+        }
+        if self.group {
+            header.push_str("\tgroup");
+        }
+        for column in &self.metadata {
+            header.push_str(&format!("\t{}", column));
+        }
+        header
+    }
+
+    fn values(&self, blocks: &[Blocks]) -> String {
+        let mut values = String::new();
+        if self.status {
+            let status = match &blocks[0].error {
+                Some(error) => format!("failed: {}", error),
+                None => "ok".to_string(),
+            };
+            values.push_str(&format!("\t{}", status));
+        }
+        if self.group {
+            values.push_str(&format!("\t{}", blocks[0].group.as_deref().unwrap_or("")));
+        }
+        for column in &self.metadata {
+            let value = blocks[0]
+                .metadata
+                .iter()
+                .find(|(c, _)| c == column)
+                .map_or("", |(_, v)| v.as_str());
+            values.push_str(&format!("\t{}", value));
+        }
+        values
+    }
+}
+
+/// A long-format table with a line per block, which is easier to parse than the summary.
+/// Samples without blocks (or for which extraction failed) have no lines.
+pub fn block_table(blocks: &[Vec<Blocks>], region: &Reg) -> String {
+    let columns = SampleColumns::new(blocks, false);
+    let mut table = format!(
+        "sample_name\tchrom\tstart\tend\tlength\tphaseset\tsupport\tfirst_record\tlast_record\toverlapping{}\n",
+        columns.header()
+    );
+    for blocks in blocks.iter().filter(|blocks| !blocks[0].empty) {
+        let sample_columns = columns.values(blocks);
+        for block in blocks {
+            table.push_str(&format!(
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}{}\n",
+                block.name,
+                region.chrom,
+                block.start,
+                block.end,
+                block.end - block.start,
+                block.phaseset,
+                block.support,
+                block.first_record,
+                block.last_record,
+                block.overlapping,
+                sample_columns
+            ));
+        }
+    }
+    table
+}
+
+/// Phasing metrics of each sample in the region, computed on the blocks clipped to the region:
+/// the number of blocks, the N50 and NG50 of their lengths (NG50 against the length of the region, 0 if the blocks
+/// cover less than half of it), the largest block, the bp covered by a block, the fraction of the region covered
+/// and the number of block boundaries (starts and ends) inside the region
+pub fn metrics(blocks: &[Vec<Blocks>], region: &Reg) -> String {
+    let columns = SampleColumns::new(blocks, true);
+    let mut table = format!(
+        "sample_name\tnum_blocks\tn50\tng50\tlargest_block\tphased_bp\tfraction_phased\tboundaries{}\n",
+        columns.header()
+    );
+    let (region_start, region_end) = (region.start as i64, region.end as i64);
+    for blocks in blocks.iter() {
+        let mut clipped: Vec<(i64, i64)> = blocks
+            .iter()
+            .filter(|block| !block.empty)
+            .map(|block| (block.start.max(region_start), block.end.min(region_end)))
+            .filter(|(start, end)| end > start)
+            .collect();
+        let boundaries = blocks
+            .iter()
+            .filter(|block| !block.empty)
+            .flat_map(|block| [block.start, block.end])
+            .filter(|&pos| pos > region_start && pos < region_end)
+            .count();
+        let mut lengths: Vec<i64> = clipped.iter().map(|(start, end)| end - start).collect();
+        lengths.sort_unstable_by(|a, b| b.cmp(a));
+        let total: i64 = lengths.iter().sum();
+        // interleaved blocks overlap, and the phased bp are counted once
+        clipped.sort_unstable();
+        let mut phased_bp = 0;
+        let mut covered_until = region_start;
+        for (start, end) in clipped {
+            phased_bp += (end - start.max(covered_until)).max(0);
+            covered_until = covered_until.max(end);
+        }
+        let region_length = region_end - region_start;
+        table.push_str(&format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{:.4}\t{}{}\n",
+            blocks[0].name,
+            lengths.len(),
+            nx(&lengths, total),
+            nx(&lengths, region_length),
+            lengths.first().unwrap_or(&0),
+            phased_bp,
+            phased_bp as f64 / region_length as f64,
+            boundaries,
+            columns.values(blocks)
+        ));
+    }
+    table
+}
+
+/// The length of the block at which the blocks, from largest to smallest, reach half of the given total, or 0
+fn nx(sorted_lengths: &[i64], total: i64) -> i64 {
+    let mut cumulative = 0;
+    for length in sorted_lengths {
+        cumulative += length;
+        if 2 * cumulative >= total {
+            return *length;
+        }
+    }
+    0
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -37,13 +216,17 @@ mod tests {
             end: 2000,
             name: "sample1".to_string(),
             empty: false,
+            ..Default::default()
         }]];
 
         let result = summarize(&blocks);
         let lines: Vec<&str> = result.lines().collect();
         assert_eq!(lines.len(), 2); // header + 1 sample
-        assert_eq!(lines[0], "sample_name\tnum_blocks\tblock_coordinates");
-        assert_eq!(lines[1], "sample1\t1\t1000-2000");
+        assert_eq!(
+            lines[0],
+            "sample_name\tnum_blocks\tblock_coordinates\tnum_overlapping\tphasesets\tsupport\trecords"
+        );
+        assert_eq!(lines[1], "sample1\t1\t1000-2000\t0\t0\t0\t0-0");
     }
 
     #[test]
@@ -54,19 +237,24 @@ mod tests {
                 end: 2000,
                 name: "sample1".to_string(),
                 empty: false,
+                ..Default::default()
             },
             Blocks {
                 start: 5000,
                 end: 7000,
                 name: "sample1".to_string(),
                 empty: false,
+                ..Default::default()
             },
         ]];
 
         let result = summarize(&blocks);
         let lines: Vec<&str> = result.lines().collect();
         assert_eq!(lines.len(), 2);
-        assert_eq!(lines[1], "sample1\t2\t1000-2000;5000-7000");
+        assert_eq!(
+            lines[1],
+            "sample1\t2\t1000-2000;5000-7000\t0\t0;0\t0;0\t0-0;0-0"
+        );
     }
 
     #[test]
@@ -77,20 +265,22 @@ mod tests {
                 end: 2000,
                 name: "sample1".to_string(),
                 empty: false,
+                ..Default::default()
             }],
             vec![Blocks {
                 start: 3000,
                 end: 4000,
                 name: "sample2".to_string(),
                 empty: false,
+                ..Default::default()
             }],
         ];
 
         let result = summarize(&blocks);
         let lines: Vec<&str> = result.lines().collect();
         assert_eq!(lines.len(), 3); // header + 2 samples
-        assert_eq!(lines[1], "sample1\t1\t1000-2000");
-        assert_eq!(lines[2], "sample2\t1\t3000-4000");
+        assert_eq!(lines[1], "sample1\t1\t1000-2000\t0\t0\t0\t0-0");
+        assert_eq!(lines[2], "sample2\t1\t3000-4000\t0\t0\t0\t0-0");
     }
 
     #[test]
@@ -100,12 +290,13 @@ mod tests {
             end: 0,
             name: "sample_empty".to_string(),
             empty: true,
+            ..Default::default()
         }]];
 
         let result = summarize(&blocks);
         let lines: Vec<&str> = result.lines().collect();
         assert_eq!(lines.len(), 2);
-        assert_eq!(lines[1], "sample_empty\t0\t0");
+        assert_eq!(lines[1], "sample_empty\t0\t0\t0\t0\t0\t0");
     }
 
     #[test]
@@ -116,19 +307,227 @@ mod tests {
                 end: 2000,
                 name: "sample1".to_string(),
                 empty: false,
+                ..Default::default()
             }],
             vec![Blocks {
                 start: 0,
                 end: 0,
                 name: "sample2".to_string(),
                 empty: true,
+                ..Default::default()
+            }],
+        ];
+
+        let result = summarize(&blocks);
+        let lines: Vec<&str> = result.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], "sample1\t1\t1000-2000\t0\t0\t0\t0-0");
+        assert_eq!(lines[2], "sample2\t0\t0\t0\t0\t0\t0");
+    }
+
+    #[test]
+    fn test_summarize_sample_sheet_columns() {
+        let blocks = vec![
+            vec![Blocks {
+                start: 1000,
+                end: 2000,
+                name: "sample1".to_string(),
+                empty: false,
+                group: Some("cases".to_string()),
+                metadata: vec![("sex".to_string(), "F".to_string())],
+                ..Default::default()
+            }],
+            vec![Blocks {
+                start: 0,
+                end: 0,
+                name: "sample2".to_string(),
+                empty: true,
+                group: Some("controls".to_string()),
+                metadata: vec![],
+                ..Default::default()
+            }],
+        ];
+
+        let result = summarize(&blocks);
+        let lines: Vec<&str> = result.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[0],
+            "sample_name\tnum_blocks\tblock_coordinates\tnum_overlapping\tphasesets\tsupport\trecords\tgroup\tsex"
+        );
+        assert_eq!(lines[1], "sample1\t1\t1000-2000\t0\t0\t0\t0-0\tcases\tF");
+        assert_eq!(lines[2], "sample2\t0\t0\t0\t0\t0\t0\tcontrols\t");
+    }
+
+    #[test]
+    fn test_summarize_overlapping() {
+        let blocks = vec![vec![
+            Blocks {
+                start: 1000,
+                end: 3000,
+                name: "sample1".to_string(),
+                empty: false,
+                overlapping: true,
+                ..Default::default()
+            },
+            Blocks {
+                start: 2000,
+                end: 4000,
+                name: "sample1".to_string(),
+                empty: false,
+                overlapping: true,
+                ..Default::default()
+            },
+        ]];
+
+        let result = summarize(&blocks);
+        let lines: Vec<&str> = result.lines().collect();
+        assert_eq!(
+            lines[1],
+            "sample1\t2\t1000-3000;2000-4000\t2\t0;0\t0;0\t0-0;0-0"
+        );
+    }
+
+    #[test]
+    fn test_summarize_block_details() {
+        let blocks = vec![vec![
+            Blocks {
+                start: 1000,
+                end: 3000,
+                name: "sample1".to_string(),
+                phaseset: 1001,
+                support: 12,
+                first_record: 1000,
+                last_record: 2500,
+                ..Default::default()
+            },
+            Blocks {
+                start: 5000,
+                end: 6000,
+                name: "sample1".to_string(),
+                phaseset: 5001,
+                support: 3,
+                first_record: 5000,
+                last_record: 5800,
+                ..Default::default()
+            },
+        ]];
+
+        let result = summarize(&blocks);
+        let lines: Vec<&str> = result.lines().collect();
+        assert_eq!(
+            lines[1],
+            "sample1\t2\t1000-3000;5000-6000\t0\t1001;5001\t12;3\t1000-2500;5000-5800"
+        );
+    }
+
+    #[test]
+    fn test_summarize_failed() {
+        let blocks = vec![
+            vec![Blocks {
+                start: 1000,
+                end: 2000,
+                name: "sample1".to_string(),
+                ..Default::default()
+            }],
+            vec![Blocks {
+                name: "sample2".to_string(),
+                empty: true,
+                error: Some("No index found for sample2.cram".to_string()),
+                ..Default::default()
             }],
         ];
 
         let result = summarize(&blocks);
         let lines: Vec<&str> = result.lines().collect();
+        assert!(lines[0].ends_with("\trecords\tstatus"));
+        assert!(lines[1].ends_with("\tok"));
+        assert_eq!(
+            lines[2],
+            "sample2\t0\t0\t0\t0\t0\t0\tfailed: No index found for sample2.cram"
+        );
+    }
+
+    #[test]
+    fn test_block_table() {
+        let region = Reg {
+            chrom: "chr7".to_string(),
+            start: 0,
+            end: 10000,
+        };
+        let blocks = vec![
+            vec![
+                Blocks {
+                    start: 1000,
+                    end: 3000,
+                    name: "sample1".to_string(),
+                    phaseset: 1001,
+                    support: 12,
+                    first_record: 1000,
+                    last_record: 2500,
+                    group: Some("cases".to_string()),
+                    ..Default::default()
+                },
+                Blocks {
+                    start: 5000,
+                    end: 6000,
+                    name: "sample1".to_string(),
+                    phaseset: 5001,
+                    group: Some("cases".to_string()),
+                    ..Default::default()
+                },
+            ],
+            vec![Blocks {
+                name: "sample2".to_string(),
+                empty: true,
+                ..Default::default()
+            }],
+        ];
+
+        let result = block_table(&blocks, &region);
+        let lines: Vec<&str> = result.lines().collect();
         assert_eq!(lines.len(), 3);
-        assert_eq!(lines[1], "sample1\t1\t1000-2000");
-        assert_eq!(lines[2], "sample2\t0\t0");
+        assert_eq!(
+            lines[0],
+            "sample_name\tchrom\tstart\tend\tlength\tphaseset\tsupport\tfirst_record\tlast_record\toverlapping\tgroup"
+        );
+        assert_eq!(
+            lines[1],
+            "sample1\tchr7\t1000\t3000\t2000\t1001\t12\t1000\t2500\tfalse\tcases"
+        );
+    }
+
+    #[test]
+    fn test_metrics() {
+        let region = Reg {
+            chrom: "chr7".to_string(),
+            start: 1000,
+            end: 11000,
+        };
+        let block = |start: i64, end: i64| Blocks {
+            start,
+            end,
+            name: "sample1".to_string(),
+            ..Default::default()
+        };
+        // the first block is clipped to 1000-4000, the second overlaps it
+        let blocks = vec![
+            vec![block(0, 4000), block(3000, 5000), block(6000, 7000)],
+            vec![Blocks {
+                name: "sample2".to_string(),
+                empty: true,
+                ..Default::default()
+            }],
+        ];
+
+        let result = metrics(&blocks, &region);
+        let lines: Vec<&str> = result.lines().collect();
+        assert_eq!(
+            lines[0],
+            "sample_name\tnum_blocks\tn50\tng50\tlargest_block\tphased_bp\tfraction_phased\tboundaries"
+        );
+        // lengths 3000, 2000 and 1000: N50 is 3000, and 5000 bp of the 10000 bp region reaches NG50 at 2000
+        assert_eq!(lines[1], "sample1\t3\t3000\t2000\t3000\t5000\t0.5000\t5");
+        assert_eq!(lines[2], "sample2\t0\t0\t0\t0\t0\t0.0000\t0");
     }
 }
@@ -1,17 +1,101 @@
-#[derive(Debug)]
+use std::path::Path;
+
+/// Split a path given as file##idx##index, the htslib notation for an index in another location,
+/// into the file and its index
+pub fn split_explicit_index(path: &Path) -> (&Path, Option<&Path>) {
+    match path.to_str().and_then(|path| path.split_once("##idx##")) {
+        Some((file, index)) => (Path::new(file), Some(Path::new(index))),
+        None => (path, None),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Reg {
     pub chrom: String,
     pub start: u32,
     pub end: u32,
 }
 
-/// parse a region string
-pub fn process_region(reg: &str) -> Result<Reg, Box<dyn std::error::Error>> {
+impl Reg {
+    /// a region spanning a whole contig, its end is set once the contig length is known
+    pub fn contig(chrom: &str) -> Reg {
+        Reg {
+            chrom: chrom.to_string(),
+            start: 0,
+            end: u32::MAX,
+        }
+    }
+
+    pub fn is_whole_contig(&self) -> bool {
+        self.start == 0 && self.end == u32::MAX
+    }
+
+    /// extend the region with flank bp on both sides, regions spanning a whole contig are kept as is.
+    /// The end is not limited to the contig length, which is only known once the inputs are read.
+    pub fn with_flank(self, flank: u32) -> Reg {
+        if self.is_whole_contig() {
+            return self;
+        }
+        Reg {
+            start: self.start.saturating_sub(flank),
+            end: self.end.saturating_add(flank),
+            ..self
+        }
+    }
+}
+
+impl std::fmt::Display for Reg {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.is_whole_contig() {
+            write!(f, "{}", self.chrom)
+        } else {
+            write!(f, "{}:{}-{}", self.chrom, self.start, self.end)
+        }
+    }
+}
+
+/// parse a region string, as chrom:start-end, chrom:pos, chrom:pos+flank or a bare contig name.
+/// Coordinates are 0-based and half-open, unless one_based is set, for 1-based and closed coordinates.
+pub fn process_region(reg: &str, one_based: bool) -> Result<Reg, Box<dyn std::error::Error>> {
     let reg = reg.replace(',', "");
-    let chrom = reg.split(':').collect::<Vec<&str>>()[0];
-    let interval = reg.split(':').collect::<Vec<&str>>()[1];
-    let start: u32 = interval.split('-').collect::<Vec<&str>>()[0].parse()?;
-    let end: u32 = interval.split('-').collect::<Vec<&str>>()[1].parse()?;
+    let Some((chrom, interval)) = reg.rsplit_once(':') else {
+        return Ok(Reg::contig(&reg));
+    };
+    if chrom.is_empty() {
+        return Err(format!("Invalid region {}: missing chromosome", reg).into());
+    }
+    let parse = |value: &str, what: &str| -> Result<u32, Box<dyn std::error::Error>> {
+        value.parse::<u32>().map_err(|_| {
+            format!(
+                "Invalid region {}: '{}' is not a valid {}",
+                reg, value, what
+            )
+            .into()
+        })
+    };
+    // with 1-based coordinates, the start (or single position) moves one to the left
+    let to_zero_based = |pos: u32| -> Result<u32, Box<dyn std::error::Error>> {
+        if one_based {
+            pos.checked_sub(1).ok_or_else(|| {
+                format!("Invalid region {}: 1-based positions start at 1", reg).into()
+            })
+        } else {
+            Ok(pos)
+        }
+    };
+    let (start, end) = if let Some((start, end)) = interval.split_once('-') {
+        (to_zero_based(parse(start, "start")?)?, parse(end, "end")?)
+    } else if let Some((pos, flank)) = interval.split_once('+') {
+        let pos = to_zero_based(parse(pos, "position")?)?;
+        let flank = parse(flank, "flank")?;
+        (
+            pos.saturating_sub(flank),
+            pos.saturating_add(flank).saturating_add(1),
+        )
+    } else {
+        let pos = to_zero_based(parse(interval, "position")?)?;
+        (pos, pos.saturating_add(1))
+    };
     if end > start {
         Ok(Reg {
             chrom: chrom.to_string(),
@@ -22,13 +106,62 @@ pub fn process_region(reg: &str) -> Result<Reg, Box<dyn std::error::Error>> {
         Err("Invalid region: begin has to be smaller than end.".into())
     }
 }
+
+/// whether a region string extends a position with a flank, as chrom:pos+flank
+pub fn has_flank(reg: &str) -> bool {
+    reg.rsplit_once(':')
+        .is_some_and(|(_, interval)| interval.contains('+'))
+}
+
+/// Regions with their (optional) name
+pub type NamedRegions = Vec<(Reg, Option<String>)>;
+
+/// parse a bed file (optionally bgzipped) of regions, with the name column (if present) as label
+pub fn parse_regions_bed(
+    path: &std::path::Path,
+) -> Result<NamedRegions, Box<dyn std::error::Error>> {
+    use std::io::BufRead;
+    let reader = std::io::BufReader::new(rust_htslib::bgzf::Reader::from_path(path)?);
+    let lines = reader.lines().collect::<Result<Vec<String>, _>>()?;
+    parse_regions(&lines)
+}
+
+fn parse_regions(lines: &[String]) -> Result<NamedRegions, Box<dyn std::error::Error>> {
+    let mut regions = vec![];
+    for line in lines {
+        if line.trim().is_empty()
+            || line.starts_with('#')
+            || line.starts_with("track")
+            || line.starts_with("browser")
+        {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 3 {
+            return Err(format!("Invalid line in regions bed file: {}", line).into());
+        }
+        let start: u32 = fields[1].parse()?;
+        let end: u32 = fields[2].parse()?;
+        if end <= start {
+            return Err(format!("Invalid region in bed file: {}", line).into());
+        }
+        let reg = Reg {
+            chrom: fields[0].to_string(),
+            start,
+            end,
+        };
+        regions.push((reg, fields.get(3).map(|name| name.to_string())));
+    }
+    Ok(regions)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_process_region_basic() {
-        let result = process_region("chr1:1000-2000").unwrap();
+        let result = process_region("chr1:1000-2000", false).unwrap();
         assert_eq!(result.chrom, "chr1");
         assert_eq!(result.start, 1000);
         assert_eq!(result.end, 2000);
@@ -36,7 +169,7 @@ mod tests {
 
     #[test]
     fn test_process_region_with_commas() {
-        let result = process_region("chr7:152,743,763-156,779,243").unwrap();
+        let result = process_region("chr7:152,743,763-156,779,243", false).unwrap();
         assert_eq!(result.chrom, "chr7");
         assert_eq!(result.start, 152743763);
         assert_eq!(result.end, 156779243);
@@ -44,7 +177,7 @@ mod tests {
 
     #[test]
     fn test_process_region_no_chr_prefix() {
-        let result = process_region("1:1000-2000").unwrap();
+        let result = process_region("1:1000-2000", false).unwrap();
         assert_eq!(result.chrom, "1");
         assert_eq!(result.start, 1000);
         assert_eq!(result.end, 2000);
@@ -52,7 +185,7 @@ mod tests {
 
     #[test]
     fn test_process_region_invalid_end_before_start() {
-        let result = process_region("chr1:2000-1000");
+        let result = process_region("chr1:2000-1000", false);
         assert!(result.is_err());
         assert!(result
             .unwrap_err()
@@ -62,19 +195,84 @@ mod tests {
 
     #[test]
     fn test_process_region_equal_start_end() {
-        let result = process_region("chr1:1000-1000");
+        let result = process_region("chr1:1000-1000", false);
         assert!(result.is_err());
     }
 
     #[test]
-    #[should_panic]
-    fn test_process_region_missing_colon() {
-        process_region("chr1-1000-2000").unwrap();
+    fn test_process_region_contig() {
+        let result = process_region("chr1", false).unwrap();
+        assert_eq!(result, Reg::contig("chr1"));
+        assert!(result.is_whole_contig());
+        assert_eq!(result.to_string(), "chr1");
+        // without a colon the whole string is a contig name
+        assert_eq!(
+            process_region("chr1-1000-2000", false).unwrap().chrom,
+            "chr1-1000-2000"
+        );
+    }
+
+    #[test]
+    fn test_region_display() {
+        let result = process_region("chr7:152,743,763-156,779,243", false).unwrap();
+        assert_eq!(result.to_string(), "chr7:152743763-156779243");
     }
 
     #[test]
-    #[should_panic]
     fn test_process_region_invalid_format() {
-        process_region("chr1:not-a-number").unwrap();
+        assert!(process_region("chr1:not-a-number", false).is_err());
+        assert!(process_region("chr1:", false).is_err());
+        assert!(process_region("chr1:1000-", false).is_err());
+        assert!(process_region(":1000-2000", false).is_err());
+        assert!(process_region("chr1:0", true).is_err());
+    }
+
+    #[test]
+    fn test_process_region_position() {
+        let result = process_region("chr1:1000", false).unwrap();
+        assert_eq!((result.start, result.end), (1000, 1001));
+        let result = process_region("chr1:1,000+500", false).unwrap();
+        assert_eq!((result.start, result.end), (500, 1501));
+        let result = process_region("chr1:100+500", false).unwrap();
+        assert_eq!((result.start, result.end), (0, 601));
+    }
+
+    #[test]
+    fn test_process_region_one_based() {
+        let result = process_region("chr1:1000-2000", true).unwrap();
+        assert_eq!((result.start, result.end), (999, 2000));
+        let result = process_region("chr1:1000", true).unwrap();
+        assert_eq!((result.start, result.end), (999, 1000));
+    }
+
+    #[test]
+    fn test_has_flank() {
+        assert!(has_flank("chr7:159345000+5000"));
+        assert!(!has_flank("chr7:159345000"));
+        assert!(!has_flank("chr7:1-2"));
+        assert!(!has_flank("chr7"));
+    }
+
+    #[test]
+    fn test_with_flank() {
+        let result = process_region("chr1:1000-2000", false)
+            .unwrap()
+            .with_flank(1500);
+        assert_eq!((result.start, result.end), (0, 3500));
+        assert!(Reg::contig("chr1").with_flank(1500).is_whole_contig());
+    }
+
+    #[test]
+    fn test_parse_regions() {
+        let lines: Vec<String> = "track name=genes\nchr7\t100\t200\tGENE1\nchr8\t300\t400\n"
+            .lines()
+            .map(str::to_string)
+            .collect();
+        let regions = parse_regions(&lines).unwrap();
+        assert_eq!(regions.len(), 2);
+        assert_eq!(regions[0].0.to_string(), "chr7:100-200");
+        assert_eq!(regions[0].1.as_deref(), Some("GENE1"));
+        assert_eq!(regions[1].1, None);
+        assert!(parse_regions(&["chr7\t200\t100".to_string()]).is_err());
     }
 }
@@ -0,0 +1,194 @@
+use crate::error::PhasiusError;
+use rust_htslib::bcf::header::HeaderView;
+use rust_htslib::bcf::record::GenotypeAllele;
+use rust_htslib::htslib;
+use std::ffi::{CStr, CString};
+use std::path::{Path, PathBuf};
+
+/// An indexed vcf/bcf, read with the synced reader of htslib, which also takes an explicit index
+/// given as file##idx##index. Unlike the indexed reader of rust-htslib, a file that cannot be opened
+/// or has no index is reported as an error.
+pub struct IndexedVcf {
+    inner: *mut htslib::bcf_srs_t,
+    header: HeaderView,
+    path: PathBuf,
+    /// the contig and (inclusive) end of the fetched region
+    region: Option<(i32, i64)>,
+    phasesets: Values,
+    genotypes: Values,
+}
+
+/// A buffer of the values of a FORMAT tag, allocated by htslib
+struct Values {
+    values: *mut i32,
+    capacity: i32,
+    /// the number of values in the current record, or a negative htslib error code
+    len: i32,
+}
+
+impl Default for Values {
+    fn default() -> Self {
+        Values {
+            values: std::ptr::null_mut(),
+            capacity: 0,
+            len: 0,
+        }
+    }
+}
+
+impl Values {
+    fn read(&mut self, header: *mut htslib::bcf_hdr_t, record: *mut htslib::bcf1_t, tag: &CStr) {
+        self.len = unsafe {
+            htslib::bcf_get_format_values(
+                header,
+                record,
+                tag.as_ptr(),
+                &mut self.values as *mut *mut i32 as *mut *mut std::ffi::c_void,
+                &mut self.capacity,
+                htslib::BCF_HT_INT as i32,
+            )
+        };
+    }
+
+    /// the values of a sample, or None if the record does not have the tag
+    fn sample(&self, sample: usize, num_samples: usize) -> Option<&[i32]> {
+        if self.len <= 0 || num_samples == 0 {
+            return None;
+        }
+        let values = unsafe { std::slice::from_raw_parts(self.values, self.len as usize) };
+        let width = values.len() / num_samples;
+        values.get(sample * width..(sample + 1) * width)
+    }
+}
+
+impl Drop for Values {
+    fn drop(&mut self) {
+        unsafe { htslib::hts_free(self.values as *mut std::ffi::c_void) };
+    }
+}
+
+/// A record of an IndexedVcf, with the values of the tags needed to build phase blocks
+pub struct VcfRecord<'a> {
+    /// 0-based position
+    pub pos: i64,
+    /// 0-based end, exclusive
+    pub end: i64,
+    /// FILTER is PASS, or no filter was applied
+    pub pass: bool,
+    num_samples: usize,
+    phasesets: &'a Values,
+    genotypes: &'a Values,
+}
+
+impl VcfRecord<'_> {
+    /// the PS of a sample, or None if the record has no (non-missing) PS for the sample
+    pub fn phaseset(&self, sample: usize) -> Option<i32> {
+        self.phasesets
+            .sample(sample, self.num_samples)?
+            .first()
+            .copied()
+            .filter(|&ps| ps != i32::MIN && ps != i32::MIN + 1)
+    }
+
+    /// the genotype of a sample, or None if the record has no GT
+    pub fn genotype(&self, sample: usize) -> Option<Vec<GenotypeAllele>> {
+        let alleles = self.genotypes.sample(sample, self.num_samples)?;
+        Some(alleles.iter().map(|&a| GenotypeAllele::from(a)).collect())
+    }
+}
+
+impl IndexedVcf {
+    pub fn from_path(path: &Path) -> Result<IndexedVcf, PhasiusError> {
+        let c_path = CString::new(path.to_string_lossy().as_bytes())
+            .map_err(|_| PhasiusError::Open(path.to_path_buf()))?;
+        let inner = unsafe { htslib::bcf_sr_init() };
+        if inner.is_null() {
+            return Err(PhasiusError::Open(path.to_path_buf()));
+        }
+        unsafe {
+            htslib::bcf_sr_set_opt(inner, htslib::bcf_sr_opt_t_BCF_SR_REQUIRE_IDX);
+            if htslib::bcf_sr_add_reader(inner, c_path.as_ptr()) != 1 {
+                let errnum = (*inner).errnum;
+                htslib::bcf_sr_destroy(inner);
+                return Err(if errnum == htslib::bcf_sr_error_idx_load_failed {
+                    PhasiusError::MissingIndex(path.to_path_buf())
+                } else {
+                    PhasiusError::Open(path.to_path_buf())
+                });
+            }
+        }
+        let header = HeaderView::new(unsafe { htslib::bcf_hdr_dup((*(*inner).readers).header) });
+        Ok(IndexedVcf {
+            inner,
+            header,
+            path: path.to_path_buf(),
+            region: None,
+            phasesets: Values::default(),
+            genotypes: Values::default(),
+        })
+    }
+
+    pub fn header(&self) -> &HeaderView {
+        &self.header
+    }
+
+    /// Jump to a region, with a 0-based start and an inclusive end
+    pub fn fetch(&mut self, rid: u32, start: u64, end: u64) -> Result<(), PhasiusError> {
+        let contig = self
+            .header
+            .rid2name(rid)
+            .map_err(PhasiusError::htslib(&self.path))?;
+        let c_contig = CString::new(contig).map_err(|_| PhasiusError::Open(self.path.clone()))?;
+        if unsafe { htslib::bcf_sr_seek(self.inner, c_contig.as_ptr(), start as i64) } != 0 {
+            return Err(PhasiusError::Htslib {
+                file: self.path.clone(),
+                source: rust_htslib::errors::Error::GenomicSeek {
+                    contig: String::from_utf8_lossy(contig).to_string(),
+                    start,
+                },
+            });
+        }
+        self.region = Some((rid as i32, end as i64));
+        Ok(())
+    }
+
+    /// The next record of the fetched region, or None at its end
+    pub fn next_record(&mut self) -> Option<Result<VcfRecord<'_>, PhasiusError>> {
+        let (rid, end) = self.region?;
+        if unsafe { htslib::bcf_sr_next_line(self.inner) } == 0 {
+            if unsafe { (*self.inner).errnum } != 0 {
+                return Some(Err(PhasiusError::Htslib {
+                    file: self.path.clone(),
+                    source: rust_htslib::errors::Error::BcfInvalidRecord,
+                }));
+            }
+            return None;
+        }
+        let (header, record) = unsafe {
+            let reader = &*(*self.inner).readers;
+            (reader.header, *reader.buffer)
+        };
+        let (record_rid, pos, rlen) = unsafe { ((*record).rid, (*record).pos, (*record).rlen) };
+        if record_rid != rid || pos > end {
+            return None;
+        }
+        self.phasesets.read(header, record, c"PS");
+        self.genotypes.read(header, record, c"GT");
+        let pass =
+            unsafe { htslib::bcf_has_filter(header, record, c"PASS".as_ptr() as *mut _) } == 1;
+        Some(Ok(VcfRecord {
+            pos,
+            end: pos + rlen,
+            pass,
+            num_samples: self.header.sample_count() as usize,
+            phasesets: &self.phasesets,
+            genotypes: &self.genotypes,
+        }))
+    }
+}
+
+impl Drop for IndexedVcf {
+    fn drop(&mut self) {
+        unsafe { htslib::bcf_sr_destroy(self.inner) };
+    }
+}